@@ -0,0 +1,40 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src"]
+mod task2 {
+    pub mod calculator;
+}
+
+use task2::calculator::number::BigInteger;
+use task2::calculator::persist::HistoryFormat;
+use task2::calculator::Calculator;
+
+// Each line of the input is evaluated as an expression, on both the i64
+// and the big-integer backend. Nothing may panic, a successful result
+// becomes `ans`, and whatever ends up in history has to survive a save
+// and load.
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut calculator = Calculator::new();
+    let mut big = Calculator::<BigInteger>::default();
+    for line in input.lines() {
+        if let Ok(result) = calculator.evaluate(line) {
+            assert_eq!(calculator.ans(), Some(&result));
+            // i64 results are exact, so the big-integer backend must agree.
+            let promoted = big.evaluate(line).expect("big integers don't overflow");
+            assert_eq!(promoted, BigInteger::from(result));
+        }
+    }
+
+    for format in [HistoryFormat::Json, HistoryFormat::Csv] {
+        let mut buffer = Vec::new();
+        calculator.save_history(&mut buffer, format).unwrap();
+        let loaded = Calculator::<i64>::load_history(&buffer[..], format).unwrap();
+        assert_eq!(loaded.show_history(), calculator.show_history());
+    }
+});
@@ -0,0 +1,34 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src"]
+mod task2 {
+    pub mod calculator;
+}
+
+use task2::calculator::persist::HistoryFormat;
+use task2::calculator::Calculator;
+
+// The first byte picks the format, the rest is the file. Loading must
+// reject bad input with an error rather than panic, and anything it does
+// accept has to save and load back to the same history.
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, file)) = data.split_first() else {
+        return;
+    };
+    let format = if selector % 2 == 0 {
+        HistoryFormat::Json
+    } else {
+        HistoryFormat::Csv
+    };
+    let Ok(calculator) = Calculator::<i64>::load_history(file, format) else {
+        return;
+    };
+
+    let mut buffer = Vec::new();
+    calculator.save_history(&mut buffer, format).unwrap();
+    let reloaded = Calculator::<i64>::load_history(&buffer[..], format).unwrap();
+    assert_eq!(reloaded.history, calculator.history);
+});
@@ -16,12 +16,39 @@
 /// 3. Implement all Calculator methods
 ///
 ///-------------------------------------------------------------------------------
+mod bounded;
+mod error;
+pub mod expression;
+pub mod format;
+mod memory;
+mod mode;
+pub mod number;
+pub mod persist;
+pub mod query;
+mod undo;
 
-#[derive(Clone)]
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+pub use bounded::EvictionPolicy;
+pub use error::{BatchError, CalcError};
+use format::PlainFormatter;
+pub use mode::ArithmeticMode;
+use number::CheckedNumber;
+use undo::{Edit, UndoStack};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OperationType {
     Addition,
     Subtraction,
     Multiplication,
+    Division,
+    Remainder,
+    Power,
+    Negate,
 }
 
 impl OperationType {
@@ -32,128 +59,465 @@ impl OperationType {
             OperationType::Addition => "+",
             OperationType::Subtraction => "-",
             OperationType::Multiplication => "*",
+            OperationType::Division => "/",
+            OperationType::Remainder => "%",
+            OperationType::Power => "^",
+            OperationType::Negate => "neg",
+        }
+    }
+
+    /// Inverse of `get_sign`.
+    pub fn from_sign(sign: &str) -> Option<Self> {
+        match sign {
+            "+" => Some(OperationType::Addition),
+            "-" => Some(OperationType::Subtraction),
+            "*" => Some(OperationType::Multiplication),
+            "/" => Some(OperationType::Division),
+            "%" => Some(OperationType::Remainder),
+            "^" => Some(OperationType::Power),
+            "neg" => Some(OperationType::Negate),
+            _ => None,
         }
     }
 
+    /// Unary operations only use their first operand; the second one is
+    /// ignored by `perform` and left out of `show_history`.
+    pub fn is_unary(&self) -> bool {
+        matches!(self, OperationType::Negate)
+    }
+
     // TODO: Perform the operation on two i64 numbers with overflow protection
     // Return Some(result) on success, None on overflow
     //
     // Example: OperationType::Multiplication.perform(x, y)
-    pub fn perform(&self, x: i64, y: i64) -> Option<i64> {
-        match self {
-            OperationType::Addition => x.checked_add(y),
-            OperationType::Subtraction => x.checked_sub(y),
-            OperationType::Multiplication => x.checked_mul(y),
+    //
+    // Division and Remainder also return None for a zero divisor, Power for
+    // an exponent that does not fit in u32. Works on any `CheckedNumber`,
+    // not just i64.
+    pub fn perform<T: CheckedNumber>(&self, x: T, y: T) -> Option<T> {
+        self.try_perform(x, y).ok()
+    }
+
+    /// Like `perform`, but reports why the operation failed.
+    pub fn try_perform<T: CheckedNumber>(&self, x: T, y: T) -> Result<T, CalcError<T>> {
+        if !x.is_valid() {
+            return Err(CalcError::InvalidOperand(x));
+        }
+        if !self.is_unary() && !y.is_valid() {
+            return Err(CalcError::InvalidOperand(y));
         }
+        let result = match self {
+            OperationType::Addition => x.checked_add(&y),
+            OperationType::Subtraction => x.checked_sub(&y),
+            OperationType::Multiplication => x.checked_mul(&y),
+            OperationType::Division | OperationType::Remainder if y.is_zero() => {
+                return Err(CalcError::DivisionByZero)
+            }
+            OperationType::Division => x.checked_div(&y),
+            OperationType::Remainder => x.checked_rem(&y),
+            OperationType::Power => match y.to_exponent() {
+                Some(exp) => x.checked_pow(exp),
+                None => return Err(CalcError::InvalidExponent(y)),
+            },
+            OperationType::Negate => x.checked_neg(),
+        };
+        result.ok_or(CalcError::Overflow {
+            op: self.clone(),
+            lhs: x,
+            rhs: y,
+        })
     }
 }
 
-#[derive(Clone)]
-pub struct Operation {
-    pub first_num: i64,
-    pub second_num: i64,
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Operation<T = i64> {
+    pub first_num: T,
+    pub second_num: T,
     pub operation_type: OperationType,
+    /// The computed result, or None if the operation overflows.
+    /// Entries stored in `Calculator::history` always have a result.
+    pub result: Option<T>,
+    /// Sequence id assigned by the calculator when the entry is recorded.
+    /// Ids increase monotonically and are never reused, even after
+    /// `clear_history`.
+    pub id: u64,
+    /// Id of the entry this one was repeated from, if any.
+    pub repeated_from: Option<u64>,
+    /// Wall-clock time the entry was recorded, when enabled with
+    /// `Calculator::set_record_timestamps`.
+    pub timestamp: Option<SystemTime>,
+    /// Whether the operation was carried out beyond the backend's native
+    /// precision, because an operand or the result needed it (see
+    /// `number::BigInteger`).
+    #[serde(default)]
+    pub promoted: bool,
+    /// The arithmetic mode the entry was computed in.
+    #[serde(default)]
+    pub mode: ArithmeticMode,
+    /// Whether the result saturated or wrapped instead of overflowing.
+    #[serde(default)]
+    pub overflowed: bool,
 }
 
-impl Operation {
+impl<T: CheckedNumber> Operation<T> {
     // TODO: Create a new Operation with the given parameters
-    pub fn new(first_num: i64, second_num: i64, operation_type: OperationType) -> Self {
+    pub fn new(first_num: T, second_num: T, operation_type: OperationType) -> Self {
+        let result = operation_type.perform(first_num.clone(), second_num.clone());
+        let promoted = Self::is_promoted(&first_num, &second_num, result.as_ref());
+        Self {
+            first_num,
+            second_num,
+            operation_type,
+            promoted,
+            result,
+            id: 0,
+            repeated_from: None,
+            timestamp: None,
+            mode: ArithmeticMode::Checked,
+            overflowed: false,
+        }
+    }
+
+    pub(crate) fn with_result(
+        first_num: T,
+        second_num: T,
+        operation_type: OperationType,
+        result: T,
+    ) -> Self {
+        let promoted = Self::is_promoted(&first_num, &second_num, Some(&result));
         Self {
             first_num,
             second_num,
             operation_type,
+            promoted,
+            result: Some(result),
+            id: 0,
+            repeated_from: None,
+            timestamp: None,
+            mode: ArithmeticMode::Checked,
+            overflowed: false,
         }
     }
+
+    /// The value of `promoted` for an entry with these operands and result.
+    pub(crate) fn is_promoted(first_num: &T, second_num: &T, result: Option<&T>) -> bool {
+        first_num.is_promoted() || second_num.is_promoted() || result.is_some_and(T::is_promoted)
+    }
 }
-pub struct Calculator {
-    pub history: Vec<Operation>,
+
+/// A calculator over any `CheckedNumber` backend, `i64` by default.
+///
+/// `Calculator::new()` creates the `i64` calculator; other backends are
+/// created with `Default`, e.g. `Calculator::<f64>::default()`.
+///
+/// History is unbounded unless a capacity is set with `with_capacity` or
+/// `set_capacity`. Entry `i` of `history` has index `first_index() + i`.
+pub struct Calculator<T = i64> {
+    pub history: Vec<Operation<T>>,
+    first_index: usize,
+    capacity: Option<usize>,
+    eviction_policy: EvictionPolicy,
+    mode: ArithmeticMode,
+    next_id: u64,
+    record_timestamps: bool,
+    undo_stack: UndoStack<T>,
+    variables: BTreeMap<String, T>,
+    memory: Option<T>,
+    ans: Option<T>,
 }
 
 impl Calculator {
     // TODO: Create a new Calculator with empty history
     pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T: CheckedNumber> Default for Calculator<T> {
+    fn default() -> Self {
         Self {
             history: Vec::new(),
+            first_index: 0,
+            capacity: None,
+            eviction_policy: EvictionPolicy::default(),
+            mode: ArithmeticMode::default(),
+            next_id: 0,
+            record_timestamps: false,
+            undo_stack: UndoStack::new(),
+            variables: BTreeMap::new(),
+            memory: None,
+            ans: None,
         }
     }
+}
+
+impl<T: CheckedNumber> Calculator<T> {
+    /// Enables or disables stamping new history entries with the current
+    /// wall-clock time. Disabled by default.
+    pub fn set_record_timestamps(&mut self, enabled: bool) {
+        self.record_timestamps = enabled;
+    }
 
     // TODO: Perform addition and store successful operations in history
     // Return Some(result) on success, None on overflow
-    pub fn addition(&mut self, x: i64, y: i64) -> Option<i64> {
-        let result = OperationType::Addition.perform(x, y);
-        if result.is_some() {
-            self.history
-                .push(Operation::new(x, y, OperationType::Addition));
-        }
-        result
+    pub fn addition(&mut self, x: T, y: T) -> Option<T> {
+        self.try_addition(x, y).ok()
     }
 
     // TODO: Perform subtraction and store successful operations in history
     // Return Some(result) on success, None on overflow
-    pub fn subtraction(&mut self, x: i64, y: i64) -> Option<i64> {
-        let result = OperationType::Subtraction.perform(x, y);
-        if result.is_some() {
-            self.history
-                .push(Operation::new(x, y, OperationType::Subtraction));
-        }
-        result
+    pub fn subtraction(&mut self, x: T, y: T) -> Option<T> {
+        self.try_subtraction(x, y).ok()
     }
 
     // TODO: Perform multiplication and store successful operations in history
     // Return Some(result) on success, None on overflow
-    pub fn multiplication(&mut self, x: i64, y: i64) -> Option<i64> {
-        let result = OperationType::Multiplication.perform(x, y);
-        if result.is_some() {
-            self.history
-                .push(Operation::new(x, y, OperationType::Multiplication));
+    pub fn multiplication(&mut self, x: T, y: T) -> Option<T> {
+        self.try_multiplication(x, y).ok()
+    }
+
+    /// Division; for integers it truncates toward zero.
+    /// Returns None when `y` is zero or the quotient overflows.
+    pub fn division(&mut self, x: T, y: T) -> Option<T> {
+        self.try_division(x, y).ok()
+    }
+
+    /// Remainder with the sign of `x`.
+    /// Returns None when `y` is zero or the operation overflows.
+    pub fn remainder(&mut self, x: T, y: T) -> Option<T> {
+        self.try_remainder(x, y).ok()
+    }
+
+    /// Raises `x` to the power of `exponent`, returning None on overflow
+    /// or if `exponent` is not a whole number that fits in u32.
+    pub fn power(&mut self, x: T, exponent: T) -> Option<T> {
+        self.try_power(x, exponent).ok()
+    }
+
+    /// Negates `x`, returning None if the result is not representable
+    /// (`i64::MIN`, or anything but zero for unsigned types).
+    pub fn negation(&mut self, x: T) -> Option<T> {
+        self.try_negation(x).ok()
+    }
+
+    pub fn try_addition(&mut self, x: T, y: T) -> Result<T, CalcError<T>> {
+        self.perform_and_record(x, y, OperationType::Addition)
+    }
+
+    pub fn try_subtraction(&mut self, x: T, y: T) -> Result<T, CalcError<T>> {
+        self.perform_and_record(x, y, OperationType::Subtraction)
+    }
+
+    pub fn try_multiplication(&mut self, x: T, y: T) -> Result<T, CalcError<T>> {
+        self.perform_and_record(x, y, OperationType::Multiplication)
+    }
+
+    pub fn try_division(&mut self, x: T, y: T) -> Result<T, CalcError<T>> {
+        self.perform_and_record(x, y, OperationType::Division)
+    }
+
+    pub fn try_remainder(&mut self, x: T, y: T) -> Result<T, CalcError<T>> {
+        self.perform_and_record(x, y, OperationType::Remainder)
+    }
+
+    pub fn try_power(&mut self, x: T, exponent: T) -> Result<T, CalcError<T>> {
+        self.perform_and_record(x, exponent, OperationType::Power)
+    }
+
+    pub fn try_negation(&mut self, x: T) -> Result<T, CalcError<T>> {
+        self.perform_and_record(x, T::zero(), OperationType::Negate)
+    }
+
+    fn perform_and_record(
+        &mut self,
+        x: T,
+        y: T,
+        operation_type: OperationType,
+    ) -> Result<T, CalcError<T>> {
+        let (operation, result) = Operation::performed(x, y, operation_type, self.mode)?;
+        self.record(vec![operation])?;
+        Ok(result)
+    }
+
+    /// Stamps `operations` with the next sequence ids (and the current time,
+    /// if enabled) and appends them to history as a single undo step. The
+    /// last result becomes `ans`.
+    ///
+    /// Fails without recording anything if history is full and the
+    /// eviction policy is `RejectNew`.
+    fn record(&mut self, mut operations: Vec<Operation<T>>) -> Result<(), CalcError<T>> {
+        let Some(last) = operations.last() else {
+            return Ok(());
+        };
+        self.check_room(operations.len())?;
+        let previous_ans = std::mem::replace(&mut self.ans, last.result.clone());
+        let timestamp = self.record_timestamps.then(SystemTime::now);
+        for operation in &mut operations {
+            operation.id = self.next_id;
+            operation.timestamp = timestamp;
+            self.next_id += 1;
         }
-        result
+        let first_index = self.end_index();
+        self.history.extend(operations.iter().cloned());
+        self.evict_excess();
+        self.undo_stack.push(Edit::Append {
+            entries: operations,
+            first_index,
+            previous_ans,
+        });
+        Ok(())
     }
 
     // TODO: Generate a formatted string showing all operations in history
     // Format: "index: first_num operation_sign second_num = result\n"
     //
     // Example: "0: 5 + 3 = 8\n1: 10 - 2 = 8\n"
+    //
+    // Unary operations are written as "index: operation_sign first_num = result\n".
+    // Entries without a result (only possible if pushed to `history` by hand)
+    // show "overflow" in its place. Results that saturated or wrapped are
+    // followed by "(saturated)" or "(wrapped)".
     pub fn show_history(&self) -> String {
-        let mut result = String::new();
-        for (i, res) in self.history.iter().enumerate() {
-            let line = format!(
-                "{}: {} {} {} = {}\n",
-                i,
-                res.first_num,
-                res.operation_type.get_sign(),
-                res.second_num,
-                res.operation_type
-                    .perform(res.first_num, res.second_num)
-                    .unwrap()
-            );
-            result.push_str(&line);
-        }
-        result
+        self.show_history_with(&PlainFormatter)
     }
 
     // TODO: Repeat an operation from history by index
     // Add the repeated operation to history and return the result
     // Return None if the index is invalid
-    pub fn repeat(&mut self, operation_index: usize) -> Option<i64> {
-        if operation_index > self.history.len() || self.history.is_empty() {
-            return None;
+    //
+    // Indexes are the ones shown by `show_history`; see `first_index`.
+    pub fn repeat(&mut self, operation_index: usize) -> Option<T> {
+        self.try_repeat(operation_index).ok()
+    }
+
+    pub fn try_repeat(&mut self, operation_index: usize) -> Result<T, CalcError<T>> {
+        let r_op = self.entry(operation_index)?;
+        let (mut repeated, res) = repetition(r_op)?;
+        repeated.repeated_from = Some(r_op.id);
+        self.record(vec![repeated])?;
+        Ok(res)
+    }
+
+    /// Repeats every entry in `range` in order, as a single undo step.
+    /// Nothing is recorded if the range is invalid or any entry fails.
+    pub fn repeat_range(&mut self, range: Range<usize>) -> Result<Vec<T>, CalcError<T>> {
+        let entries = self.entries(range)?;
+        let mut repeated = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (mut operation, _) = repetition(entry)?;
+            operation.repeated_from = Some(entry.id);
+            repeated.push(operation);
         }
-        let r_op = self.history.get(operation_index).unwrap();
-        let res = r_op
-            .operation_type
-            .perform(r_op.first_num, r_op.second_num)
-            .unwrap();
-        self.history.push(Operation::new(
-            r_op.first_num,
-            r_op.second_num,
-            r_op.operation_type.clone(),
-        ));
-        Some(res)
+        let results = repeated.iter().filter_map(|op| op.result.clone()).collect();
+        self.record(repeated)?;
+        Ok(results)
+    }
+
+    /// Performs the operation of history entry `index` again with new
+    /// operands, in the entry's arithmetic mode. The new entry is linked
+    /// to the original via `repeated_from`. `new_rhs` is ignored for unary
+    /// operations.
+    pub fn replay_with(&mut self, index: usize, new_lhs: T, new_rhs: T) -> Result<T, CalcError<T>> {
+        let entry = self.entry(index)?;
+        let operation_type = entry.operation_type.clone();
+        let new_rhs = if operation_type.is_unary() {
+            T::zero()
+        } else {
+            new_rhs
+        };
+        let (mut replayed, res) =
+            Operation::performed(new_lhs, new_rhs, operation_type, entry.mode)?;
+        replayed.repeated_from = Some(entry.id);
+        self.record(vec![replayed])?;
+        Ok(res)
+    }
+
+    /// Applies `operations` in order, recomputing each one from its
+    /// operands in the calculator's current mode; stored results, modes,
+    /// ids and timestamps are ignored.
+    ///
+    /// The batch is atomic: either every operation succeeds and they are
+    /// appended to history as a single undo step, or nothing is recorded
+    /// and the first failure is returned with its position.
+    pub fn batch(&mut self, operations: &[Operation<T>]) -> Result<Vec<T>, BatchError<T>> {
+        let mut performed = Vec::with_capacity(operations.len());
+        for (position, op) in operations.iter().enumerate() {
+            let (operation, _) = Operation::performed(
+                op.first_num.clone(),
+                op.second_num.clone(),
+                op.operation_type.clone(),
+                self.mode,
+            )
+            .map_err(|error| BatchError { position, error })?;
+            performed.push(operation);
+        }
+        let results = performed
+            .iter()
+            .filter_map(|op| op.result.clone())
+            .collect();
+        self.record(performed).map_err(|error| BatchError {
+            position: self.room().unwrap_or(0),
+            error,
+        })?;
+        Ok(results)
     }
 
     // TODO: Clear all operations from history
+    //
+    // The cleared entries can be restored with `undo`. Indexes start from
+    // zero again afterwards.
     pub fn clear_history(&mut self) {
-        self.history.clear();
+        if !self.history.is_empty() {
+            let entries = std::mem::take(&mut self.history);
+            let first_index = std::mem::take(&mut self.first_index);
+            self.undo_stack.push(Edit::Clear {
+                entries,
+                first_index,
+            });
+        }
     }
+
+    /// Parses and evaluates an infix expression such as `(3 + 4) * -2 - 7`.
+    ///
+    /// Expressions may refer to variables, `ans` and the memory register
+    /// `MR`, and `let x = 3 * 4` stores the result in `x` as well as
+    /// returning it.
+    ///
+    /// Every intermediate operation is appended to history in evaluation
+    /// order. Nothing is recorded unless the whole expression succeeds.
+    pub fn evaluate(&mut self, expr: &str) -> Result<T, CalcError<T>> {
+        let statement = expression::parse::<T>(expr)?;
+        let mut steps = Vec::new();
+        let result = statement
+            .expr
+            .eval(self.mode, &|name| self.value_of(name), &mut steps)?;
+        self.record(steps)?;
+        self.ans = Some(result.clone());
+        if let Some(name) = statement.binding {
+            self.variables.insert(name, result.clone());
+        }
+        Ok(result)
+    }
+}
+
+/// A fresh copy of `entry` for `repeat`: the stored result is reused, or
+/// recomputed in the entry's mode if there is none.
+fn repetition<T: CheckedNumber>(entry: &Operation<T>) -> Result<(Operation<T>, T), CalcError<T>> {
+    let Some(result) = entry.result.clone() else {
+        return Operation::performed(
+            entry.first_num.clone(),
+            entry.second_num.clone(),
+            entry.operation_type.clone(),
+            entry.mode,
+        );
+    };
+    let mut operation = Operation::with_result(
+        entry.first_num.clone(),
+        entry.second_num.clone(),
+        entry.operation_type.clone(),
+        result.clone(),
+    );
+    operation.mode = entry.mode;
+    operation.overflowed = entry.overflowed;
+    Ok((operation, result))
 }
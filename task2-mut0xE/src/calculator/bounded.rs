@@ -0,0 +1,121 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::number::CheckedNumber;
+use super::{CalcError, Calculator, Operation};
+
+/// What a capacity-bounded `Calculator` does when history is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EvictionPolicy {
+    /// Evict the oldest entries to make room, like a ring buffer.
+    #[default]
+    DropOldest,
+    /// Keep history as it is and fail the operation with
+    /// `CalcError::HistoryFull`.
+    RejectNew,
+}
+
+impl Calculator {
+    /// Creates an `i64` calculator that keeps at most `capacity` history
+    /// entries, handling the rest according to `policy`.
+    pub fn with_capacity(capacity: usize, policy: EvictionPolicy) -> Self {
+        let mut calculator = Self::new();
+        calculator.set_capacity(Some(capacity), policy);
+        calculator
+    }
+}
+
+impl<T: CheckedNumber> Calculator<T> {
+    /// Bounds history to `capacity` entries, or lifts the bound with None.
+    /// If history is already longer, the oldest entries are evicted
+    /// whatever the policy.
+    pub fn set_capacity(&mut self, capacity: Option<usize>, policy: EvictionPolicy) {
+        self.capacity = capacity;
+        self.eviction_policy = policy;
+        self.evict_excess();
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// The index of `history[0]`.
+    ///
+    /// Indexes stay the same when older entries are evicted, so `repeat`
+    /// keeps referring to the same entry; they restart at zero when
+    /// history is cleared.
+    pub fn first_index(&self) -> usize {
+        self.first_index
+    }
+
+    /// One past the index of the newest entry.
+    pub fn end_index(&self) -> usize {
+        self.first_index + self.history.len()
+    }
+
+    /// The entry with the given index.
+    pub fn entry(&self, index: usize) -> Result<&Operation<T>, CalcError<T>> {
+        if index < self.first_index {
+            return Err(CalcError::EvictedHistoryIndex {
+                index,
+                first: self.first_index,
+            });
+        }
+        self.history
+            .get(index - self.first_index)
+            .ok_or(CalcError::InvalidHistoryIndex {
+                index,
+                len: self.end_index(),
+            })
+    }
+
+    /// The entries with indexes in `range`.
+    pub fn entries(&self, range: Range<usize>) -> Result<&[Operation<T>], CalcError<T>> {
+        if range.start < self.first_index && range.start < range.end {
+            return Err(CalcError::EvictedHistoryIndex {
+                index: range.start,
+                first: self.first_index,
+            });
+        }
+        let physical = range.start.saturating_sub(self.first_index)
+            ..range.end.saturating_sub(self.first_index);
+        self.history
+            .get(physical)
+            .ok_or(CalcError::InvalidHistoryRange {
+                start: range.start,
+                end: range.end,
+                len: self.end_index(),
+            })
+    }
+
+    /// How many more entries fit before the capacity is reached, or None
+    /// if history is unbounded.
+    pub(super) fn room(&self) -> Option<usize> {
+        self.capacity
+            .map(|capacity| capacity.saturating_sub(self.history.len()))
+    }
+
+    /// Fails with `HistoryFull` if `incoming` entries can't be added under
+    /// the `RejectNew` policy.
+    pub(super) fn check_room(&self, incoming: usize) -> Result<(), CalcError<T>> {
+        match (self.eviction_policy, self.capacity, self.room()) {
+            (EvictionPolicy::RejectNew, Some(capacity), Some(room)) if incoming > room => {
+                Err(CalcError::HistoryFull { capacity })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Drops the oldest entries until history fits its capacity.
+    pub(super) fn evict_excess(&mut self) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        let excess = self.history.len().saturating_sub(capacity);
+        if excess > 0 {
+            self.history.drain(..excess);
+            self.first_index += excess;
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+
+use super::expression::{ParseError, ParseErrorKind};
+use super::number::CheckedNumber;
+use super::OperationType;
+
+/// Reasons a calculator operation can fail.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalcError<T = i64> {
+    /// The result of `op` applied to `lhs` and `rhs` can't be represented
+    /// by the number type.
+    Overflow {
+        op: OperationType,
+        lhs: T,
+        rhs: T,
+    },
+    DivisionByZero,
+    /// `Power` was given an exponent that is not a whole number in `u32`.
+    InvalidExponent(T),
+    /// An operand the number type rejects outright, such as a NaN float.
+    InvalidOperand(T),
+    InvalidHistoryIndex {
+        index: usize,
+        len: usize,
+    },
+    /// An index whose entry was evicted from a capacity-bounded history;
+    /// `first` is the oldest index still available.
+    EvictedHistoryIndex {
+        index: usize,
+        first: usize,
+    },
+    /// History is at capacity and the eviction policy is `RejectNew`.
+    HistoryFull {
+        capacity: usize,
+    },
+    /// A history range that is reversed or extends past the end.
+    InvalidHistoryRange {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    /// A name that has not been bound with `let` or `set_variable`, or
+    /// `ans` before anything has been calculated.
+    UnknownVariable(String),
+    /// A name `set_variable` can't bind: not an identifier, or reserved.
+    InvalidVariableName(String),
+    ParseError(ParseError),
+}
+
+impl<T: CheckedNumber> fmt::Display for CalcError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalcError::Overflow { op, lhs, .. } if op.is_unary() => {
+                write!(
+                    f,
+                    "overflow computing {} {}",
+                    op.get_sign(),
+                    lhs.format_value()
+                )
+            }
+            CalcError::Overflow { op, lhs, rhs } => write!(
+                f,
+                "overflow computing {} {} {}",
+                lhs.format_value(),
+                op.get_sign(),
+                rhs.format_value()
+            ),
+            CalcError::DivisionByZero => write!(f, "division by zero"),
+            CalcError::InvalidExponent(exponent) => {
+                write!(f, "invalid exponent {}", exponent.format_value())
+            }
+            CalcError::InvalidOperand(operand) => {
+                write!(f, "invalid operand {}", operand.format_value())
+            }
+            CalcError::InvalidHistoryIndex { index, len } => write!(
+                f,
+                "history index {} is out of range for {} entries",
+                index, len
+            ),
+            CalcError::EvictedHistoryIndex { index, first } => write!(
+                f,
+                "history index {} has been evicted, the oldest entry is {}",
+                index, first
+            ),
+            CalcError::HistoryFull { capacity } => {
+                write!(f, "history is full ({} entries)", capacity)
+            }
+            CalcError::InvalidHistoryRange { start, end, len } => write!(
+                f,
+                "history range {}..{} is out of range for {} entries",
+                start, end, len
+            ),
+            CalcError::UnknownVariable(name) => write!(f, "unknown variable {}", name),
+            CalcError::InvalidVariableName(name) => {
+                write!(f, "{:?} can't be used as a variable name", name)
+            }
+            CalcError::ParseError(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<T: CheckedNumber> std::error::Error for CalcError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CalcError::ParseError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The first failing operation of `Calculator::batch`, by its position in
+/// the batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchError<T = i64> {
+    pub position: usize,
+    pub error: CalcError<T>,
+}
+
+impl<T: CheckedNumber> fmt::Display for BatchError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "operation {} of the batch failed: {}",
+            self.position, self.error
+        )
+    }
+}
+
+impl<T: CheckedNumber + 'static> std::error::Error for BatchError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<T> From<ParseError> for CalcError<T> {
+    fn from(err: ParseError) -> Self {
+        CalcError::ParseError(err)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c)?,
+            ParseErrorKind::UnexpectedToken => write!(f, "unexpected token")?,
+            ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end of input")?,
+            ParseErrorKind::UnclosedParen => write!(f, "unclosed parenthesis")?,
+            ParseErrorKind::NumberTooLarge => write!(f, "number too large")?,
+            ParseErrorKind::InvalidNumber => write!(f, "invalid number")?,
+            ParseErrorKind::ReservedName => write!(f, "reserved name")?,
+            ParseErrorKind::TooDeep => write!(f, "expression nested too deeply")?,
+        }
+        write!(f, " at {}..{}", self.span.start, self.span.end)
+    }
+}
+
+impl std::error::Error for ParseError {}
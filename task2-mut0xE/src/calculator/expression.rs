@@ -0,0 +1,445 @@
+use std::ops::Range;
+
+use super::number::CheckedNumber;
+use super::{ArithmeticMode, CalcError, Operation, OperationType};
+
+/// Reasons an expression can fail to parse.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedChar(char),
+    UnexpectedToken,
+    UnexpectedEnd,
+    UnclosedParen,
+    NumberTooLarge,
+    /// A decimal literal used with a backend that only has whole numbers.
+    InvalidNumber,
+    /// `let` used to bind `ans`, `MR` or `let` itself.
+    ReservedName,
+    /// Parentheses, exponents or minus signs nested more than
+    /// `MAX_DEPTH` deep.
+    TooDeep,
+}
+
+/// A syntax error together with the byte span of the offending token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Range<usize>,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number,
+    Ident,
+    Equals,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '^' => TokenKind::Caret,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '=' => TokenKind::Equals,
+            '0'..='9' => {
+                // digits ("." digits)?
+                let bytes = input.as_bytes();
+                let digits_from =
+                    |i: usize| i + bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+                let mut end = digits_from(start);
+                if bytes.get(end) == Some(&b'.')
+                    && bytes.get(end + 1).is_some_and(u8::is_ascii_digit)
+                {
+                    end = digits_from(end + 1);
+                }
+                while chars.peek().is_some_and(|&(i, _)| i < end) {
+                    chars.next();
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Number,
+                    span: start..end,
+                });
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let end = start
+                    + input[start..]
+                        .bytes()
+                        .take_while(|b| b.is_ascii_alphanumeric() || *b == b'_')
+                        .count();
+                while chars.peek().is_some_and(|&(i, _)| i < end) {
+                    chars.next();
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Ident,
+                    span: start..end,
+                });
+                continue;
+            }
+            other => {
+                return Err(ParseError::new(
+                    ParseErrorKind::UnexpectedChar(other),
+                    start..start + other.len_utf8(),
+                ))
+            }
+        };
+        tokens.push(Token {
+            kind,
+            span: start..start + c.len_utf8(),
+        });
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr<T> {
+    Literal(T),
+    /// A variable, `ans` or `MR`, looked up when the expression is evaluated.
+    Variable(String),
+    Negate(Box<Expr<T>>),
+    /// `first` followed by left-associative operations, such as
+    /// `1 + 2 - 3`. Kept flat rather than as nested `Binary` nodes, so a
+    /// long chain doesn't make evaluating or dropping it recurse deeply.
+    Chain {
+        first: Box<Expr<T>>,
+        rest: Vec<(OperationType, Expr<T>)>,
+    },
+    Binary {
+        operation_type: OperationType,
+        lhs: Box<Expr<T>>,
+        rhs: Box<Expr<T>>,
+    },
+}
+
+impl<T: CheckedNumber> Expr<T> {
+    /// Evaluates the tree bottom-up, pushing every intermediate operation
+    /// onto `steps` in the order it was performed. Variables are resolved
+    /// with `lookup`.
+    pub(crate) fn eval(
+        &self,
+        mode: ArithmeticMode,
+        lookup: &dyn Fn(&str) -> Result<T, CalcError<T>>,
+        steps: &mut Vec<Operation<T>>,
+    ) -> Result<T, CalcError<T>> {
+        let (step, result) = match self {
+            Expr::Literal(value) => return Ok(value.clone()),
+            Expr::Variable(name) => return lookup(name),
+            Expr::Negate(operand) => {
+                let value = operand.eval(mode, lookup, steps)?;
+                Operation::performed(value, T::zero(), OperationType::Negate, mode)?
+            }
+            Expr::Chain { first, rest } => {
+                let mut x = first.eval(mode, lookup, steps)?;
+                for (operation_type, rhs) in rest {
+                    let y = rhs.eval(mode, lookup, steps)?;
+                    let (step, result) = Operation::performed(x, y, operation_type.clone(), mode)?;
+                    steps.push(step);
+                    x = result;
+                }
+                return Ok(x);
+            }
+            Expr::Binary {
+                operation_type,
+                lhs,
+                rhs,
+            } => {
+                let x = lhs.eval(mode, lookup, steps)?;
+                let y = rhs.eval(mode, lookup, steps)?;
+                Operation::performed(x, y, operation_type.clone(), mode)?
+            }
+        };
+        steps.push(step);
+        Ok(result)
+    }
+}
+
+/// An expression, optionally bound to a variable with `let name = ...`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Statement<T> {
+    pub(crate) binding: Option<String>,
+    pub(crate) expr: Expr<T>,
+}
+
+/// How deeply parentheses, exponents and minus signs may nest. Parsing and
+/// evaluation recurse once per level, so without a limit a long enough
+/// input would overflow the stack.
+pub const MAX_DEPTH: usize = 256;
+
+/// Names that can be read in an expression but not bound with `let`.
+pub(crate) const RESERVED_NAMES: [&str; 3] = ["let", "ans", "MR"];
+
+/// Parses an infix expression over `+`, `-`, `*`, `/`, `%`, `^`, unary
+/// minus, parentheses and variable names, optionally prefixed with
+/// `let name =`. `^` binds tightest and is right associative, the other
+/// binary operators are left associative.
+///
+/// Literals are parsed with `T`'s `FromStr`, so `1.5` is only accepted by
+/// backends that have fractional values. Nesting deeper than `MAX_DEPTH`
+/// fails with `ParseErrorKind::TooDeep`.
+pub(crate) fn parse<T: CheckedNumber>(input: &str) -> Result<Statement<T>, ParseError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        input,
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let binding = parser.binding()?;
+    let expr = parser.expression()?;
+    match parser.peek() {
+        None => Ok(Statement { binding, expr }),
+        Some(token) => Err(ParseError::new(
+            ParseErrorKind::UnexpectedToken,
+            token.span.clone(),
+        )),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    /// How many parentheses, exponents and minus signs enclose the token
+    /// at `pos`.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, ParseError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or(ParseError::new(
+            ParseErrorKind::UnexpectedEnd,
+            self.input.len()..self.input.len(),
+        ))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Goes one level deeper for the token at `span`, failing with
+    /// `TooDeep` past `MAX_DEPTH`.
+    fn enter(&mut self, span: Range<usize>) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ParseError::new(ParseErrorKind::TooDeep, span));
+        }
+        Ok(())
+    }
+
+    // binding := ("let" ident "=")?
+    fn binding(&mut self) -> Result<Option<String>, ParseError> {
+        let is_let = matches!(
+            self.peek(),
+            Some(token) if token.kind == TokenKind::Ident && &self.input[token.span.clone()] == "let"
+        );
+        if !is_let {
+            return Ok(None);
+        }
+        self.pos += 1;
+        let name = self.next()?;
+        if name.kind != TokenKind::Ident {
+            return Err(ParseError::new(ParseErrorKind::UnexpectedToken, name.span));
+        }
+        let text = &self.input[name.span.clone()];
+        if RESERVED_NAMES.contains(&text) {
+            return Err(ParseError::new(ParseErrorKind::ReservedName, name.span));
+        }
+        let equals = self.next()?;
+        if equals.kind != TokenKind::Equals {
+            return Err(ParseError::new(
+                ParseErrorKind::UnexpectedToken,
+                equals.span,
+            ));
+        }
+        Ok(Some(text.to_string()))
+    }
+
+    // expression := term (("+" | "-") term)*
+    fn expression<T: CheckedNumber>(&mut self) -> Result<Expr<T>, ParseError> {
+        let first = self.term()?;
+        let mut rest = Vec::new();
+        while let Some(token) = self.peek() {
+            let operation_type = match token.kind {
+                TokenKind::Plus => OperationType::Addition,
+                TokenKind::Minus => OperationType::Subtraction,
+                _ => break,
+            };
+            self.pos += 1;
+            rest.push((operation_type, self.term()?));
+        }
+        Ok(chain(first, rest))
+    }
+
+    // term := unary (("*" | "/" | "%") unary)*
+    fn term<T: CheckedNumber>(&mut self) -> Result<Expr<T>, ParseError> {
+        let first = self.unary()?;
+        let mut rest = Vec::new();
+        while let Some(token) = self.peek() {
+            let operation_type = match token.kind {
+                TokenKind::Star => OperationType::Multiplication,
+                TokenKind::Slash => OperationType::Division,
+                TokenKind::Percent => OperationType::Remainder,
+                _ => break,
+            };
+            self.pos += 1;
+            rest.push((operation_type, self.unary()?));
+        }
+        Ok(chain(first, rest))
+    }
+
+    // unary := "-"* power
+    fn unary<T: CheckedNumber>(&mut self) -> Result<Expr<T>, ParseError> {
+        let mut minuses = 0;
+        let mut last_minus = None;
+        while let Some(token) = self.peek().filter(|token| token.kind == TokenKind::Minus) {
+            let span = token.span.clone();
+            self.enter(span.clone())?;
+            self.pos += 1;
+            minuses += 1;
+            last_minus = Some(span);
+        }
+
+        let mut operand = None;
+        // A minus directly in front of a literal is part of the literal, so
+        // `-2` stays a single value and `i64::MIN` can be written out. This
+        // does not apply to `-2 ^ 2`, which negates the power, nor to types
+        // without negative literals, where `-2` is evaluated as a negation.
+        let raises = matches!(
+            self.tokens.get(self.pos + 1),
+            Some(token) if token.kind == TokenKind::Caret
+        );
+        if let (
+            Some(span),
+            Some(Token {
+                kind: TokenKind::Number,
+                span: number_span,
+            }),
+        ) = (last_minus, self.peek().cloned().filter(|_| !raises))
+        {
+            let negative = format!("-{}", &self.input[number_span.clone()]);
+            if let Ok(value) = negative.parse::<T>() {
+                self.pos += 1;
+                minuses -= 1;
+                operand = Some(Expr::Literal(value));
+            } else if self.literal::<T>(number_span.clone()).is_err() {
+                return Err(ParseError::new(
+                    ParseErrorKind::NumberTooLarge,
+                    span.start..number_span.end,
+                ));
+            }
+        }
+
+        let mut expr = match operand {
+            Some(literal) => literal,
+            None => self.power()?,
+        };
+        for _ in 0..minuses {
+            expr = Expr::Negate(Box::new(expr));
+        }
+        self.depth -= minuses;
+        Ok(expr)
+    }
+
+    // power := primary ("^" unary)?
+    fn power<T: CheckedNumber>(&mut self) -> Result<Expr<T>, ParseError> {
+        let base = self.primary()?;
+        let is_caret = matches!(self.peek(), Some(token) if token.kind == TokenKind::Caret);
+        if !is_caret {
+            return Ok(base);
+        }
+        let caret = self.next()?.span;
+        self.enter(caret)?;
+        let exponent = self.unary()?;
+        self.depth -= 1;
+        Ok(Expr::Binary {
+            operation_type: OperationType::Power,
+            lhs: Box::new(base),
+            rhs: Box::new(exponent),
+        })
+    }
+
+    // primary := number | ident | "(" expression ")"
+    fn primary<T: CheckedNumber>(&mut self) -> Result<Expr<T>, ParseError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Number => self.literal(token.span).map(Expr::Literal),
+            TokenKind::Ident if &self.input[token.span.clone()] != "let" => {
+                Ok(Expr::Variable(self.input[token.span].to_string()))
+            }
+            TokenKind::LParen => {
+                self.enter(token.span.clone())?;
+                let inner = self.expression()?;
+                self.depth -= 1;
+                match self.peek() {
+                    Some(Token {
+                        kind: TokenKind::RParen,
+                        ..
+                    }) => {
+                        self.pos += 1;
+                        Ok(inner)
+                    }
+                    Some(other) => Err(ParseError::new(
+                        ParseErrorKind::UnexpectedToken,
+                        other.span.clone(),
+                    )),
+                    None => Err(ParseError::new(ParseErrorKind::UnclosedParen, token.span)),
+                }
+            }
+            _ => Err(ParseError::new(ParseErrorKind::UnexpectedToken, token.span)),
+        }
+    }
+
+    fn literal<T: CheckedNumber>(&self, span: Range<usize>) -> Result<T, ParseError> {
+        let text = &self.input[span.clone()];
+        text.parse::<T>().map_err(|_| {
+            let kind = if text.contains('.') {
+                ParseErrorKind::InvalidNumber
+            } else {
+                ParseErrorKind::NumberTooLarge
+            };
+            ParseError::new(kind, span)
+        })
+    }
+}
+
+fn chain<T>(first: Expr<T>, rest: Vec<(OperationType, Expr<T>)>) -> Expr<T> {
+    if rest.is_empty() {
+        return first;
+    }
+    Expr::Chain {
+        first: Box::new(first),
+        rest,
+    }
+}
@@ -0,0 +1,187 @@
+use serde::Serialize;
+
+use super::number::CheckedNumber;
+use super::{ArithmeticMode, Calculator, Operation};
+
+/// Renders a calculator's history as text. Used by
+/// `Calculator::show_history_with`.
+pub trait HistoryFormatter<T = i64> {
+    /// Formats `history`, whose first entry has index `first_index`.
+    fn format(&self, history: &[Operation<T>], first_index: usize) -> String;
+}
+
+/// The `show_history` layout: `index: a op b = r`, or `index: op a = r`
+/// for unary operations, one entry per line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainFormatter;
+
+/// A Markdown table with `#`, `lhs`, `op`, `rhs` and `result` columns.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownFormatter;
+
+/// One JSON object per line with the entry's `index`, `id`, `lhs`, `op`,
+/// `rhs` and `result`. `rhs` is null for unary operations, `result` for
+/// entries without one. Entries computed in a saturating or wrapping mode
+/// also have `mode`, and `overflowed` if the result saturated or wrapped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonLinesFormatter;
+
+/// The plain layout padded into right-aligned columns. Unary operations
+/// put their sign in the operator column and their operand on the right.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlignedFormatter;
+
+/// The text of one entry's columns, shared by the text formatters.
+struct Row {
+    index: String,
+    lhs: String,
+    sign: String,
+    rhs: String,
+    result: String,
+}
+
+impl Row {
+    fn new<T: CheckedNumber>(index: usize, op: &Operation<T>) -> Self {
+        let (lhs, rhs) = if op.operation_type.is_unary() {
+            (String::new(), op.first_num.format_value())
+        } else {
+            (op.first_num.format_value(), op.second_num.format_value())
+        };
+        Row {
+            index: index.to_string(),
+            lhs,
+            sign: op.operation_type.get_sign().to_string(),
+            rhs,
+            result: match (&op.result, op.mode.overflow_label()) {
+                (Some(value), Some(label)) if op.overflowed => {
+                    format!("{} ({})", value.format_value(), label)
+                }
+                (Some(value), _) => value.format_value(),
+                (None, _) => "overflow".to_string(),
+            },
+        }
+    }
+}
+
+fn rows<T: CheckedNumber>(
+    history: &[Operation<T>],
+    first_index: usize,
+) -> impl Iterator<Item = Row> + '_ {
+    history
+        .iter()
+        .enumerate()
+        .map(move |(i, op)| Row::new(first_index + i, op))
+}
+
+impl<T: CheckedNumber> HistoryFormatter<T> for PlainFormatter {
+    fn format(&self, history: &[Operation<T>], first_index: usize) -> String {
+        rows(history, first_index)
+            .map(|row| {
+                if row.lhs.is_empty() {
+                    format!("{}: {} {} = {}\n", row.index, row.sign, row.rhs, row.result)
+                } else {
+                    format!(
+                        "{}: {} {} {} = {}\n",
+                        row.index, row.lhs, row.sign, row.rhs, row.result
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+impl<T: CheckedNumber> HistoryFormatter<T> for MarkdownFormatter {
+    fn format(&self, history: &[Operation<T>], first_index: usize) -> String {
+        let mut table = String::from("| # | lhs | op | rhs | result |\n|---|---|---|---|---|\n");
+        for row in rows(history, first_index) {
+            let (lhs, rhs) = if row.lhs.is_empty() {
+                (row.rhs, String::new())
+            } else {
+                (row.lhs, row.rhs)
+            };
+            table.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                row.index, lhs, row.sign, rhs, row.result
+            ));
+        }
+        table
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a, T> {
+    index: usize,
+    id: u64,
+    lhs: &'a T,
+    op: &'a str,
+    rhs: Option<&'a T>,
+    result: Option<&'a T>,
+    #[serde(skip_serializing_if = "ArithmeticMode::is_checked")]
+    mode: ArithmeticMode,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    overflowed: bool,
+}
+
+impl<T: CheckedNumber + Serialize> HistoryFormatter<T> for JsonLinesFormatter {
+    fn format(&self, history: &[Operation<T>], first_index: usize) -> String {
+        let mut lines = String::new();
+        for (i, op) in history.iter().enumerate() {
+            let line = JsonLine {
+                index: first_index + i,
+                id: op.id,
+                lhs: &op.first_num,
+                op: op.operation_type.get_sign(),
+                rhs: (!op.operation_type.is_unary()).then_some(&op.second_num),
+                result: op.result.as_ref(),
+                mode: op.mode,
+                overflowed: op.overflowed,
+            };
+            lines.push_str(&serde_json::to_string(&line).expect("history entries are valid JSON"));
+            lines.push('\n');
+        }
+        lines
+    }
+}
+
+impl<T: CheckedNumber> HistoryFormatter<T> for AlignedFormatter {
+    fn format(&self, history: &[Operation<T>], first_index: usize) -> String {
+        let rows: Vec<Row> = rows(history, first_index).collect();
+        let width = |column: fn(&Row) -> &str| {
+            rows.iter()
+                .map(|row| column(row).chars().count())
+                .max()
+                .unwrap_or(0)
+        };
+        let index_width = width(|row| &row.index);
+        let lhs_width = width(|row| &row.lhs);
+        let sign_width = width(|row| &row.sign);
+        let rhs_width = width(|row| &row.rhs);
+        let result_width = width(|row| &row.result);
+
+        rows.iter()
+            .map(|row| {
+                format!(
+                    "{:>iw$}: {:>lw$} {:<sw$} {:>rw$} = {:>ow$}\n",
+                    row.index,
+                    row.lhs,
+                    row.sign,
+                    row.rhs,
+                    row.result,
+                    iw = index_width,
+                    lw = lhs_width,
+                    sw = sign_width,
+                    rw = rhs_width,
+                    ow = result_width,
+                )
+            })
+            .collect()
+    }
+}
+
+impl<T: CheckedNumber> Calculator<T> {
+    /// Renders history with the given formatter, e.g.
+    /// `calculator.show_history_with(&MarkdownFormatter)`.
+    pub fn show_history_with(&self, formatter: &dyn HistoryFormatter<T>) -> String {
+        formatter.format(&self.history, self.first_index())
+    }
+}
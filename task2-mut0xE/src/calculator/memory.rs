@@ -0,0 +1,96 @@
+use super::expression::RESERVED_NAMES;
+use super::number::CheckedNumber;
+use super::{CalcError, Calculator, OperationType};
+
+impl<T: CheckedNumber> Calculator<T> {
+    /// Binds `name` to `value`, replacing any previous value.
+    ///
+    /// Names follow the expression syntax (a letter or `_`, then letters,
+    /// digits or `_`) and can't be `let`, `ans` or `MR`.
+    pub fn set_variable(&mut self, name: &str, value: T) -> Result<(), CalcError<T>> {
+        let mut chars = name.chars();
+        let is_ident = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_ident || RESERVED_NAMES.contains(&name) {
+            return Err(CalcError::InvalidVariableName(name.to_string()));
+        }
+        self.variables.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Removes a variable, returning its value if it was set.
+    pub fn remove_variable(&mut self, name: &str) -> Option<T> {
+        self.variables.remove(name)
+    }
+
+    /// All variables in name order, without `ans` and `MR`.
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// The result of the most recent successful calculation.
+    pub fn ans(&self) -> Option<&T> {
+        self.ans.as_ref()
+    }
+
+    /// Looks up a name the way `evaluate` does: `ans`, `MR` or a variable.
+    /// Use it to pass stored values to the arithmetic methods:
+    ///
+    /// `calculator.addition(calculator.value_of("x")?, 1)`
+    pub fn value_of(&self, name: &str) -> Result<T, CalcError<T>> {
+        let value = match name {
+            "ans" => self.ans.clone(),
+            "MR" => Some(self.memory_recall()),
+            _ => self.variables.get(name).cloned(),
+        };
+        value.ok_or_else(|| CalcError::UnknownVariable(name.to_string()))
+    }
+
+    /// M+: adds `value` to the memory register and returns the new total.
+    /// The register is left unchanged if the sum overflows.
+    pub fn memory_add(&mut self, value: T) -> Result<T, CalcError<T>> {
+        self.update_memory(value, OperationType::Addition)
+    }
+
+    /// M-: subtracts `value` from the memory register and returns the new
+    /// total. The register is left unchanged if the difference overflows.
+    pub fn memory_subtract(&mut self, value: T) -> Result<T, CalcError<T>> {
+        self.update_memory(value, OperationType::Subtraction)
+    }
+
+    /// MR: the value of the memory register, zero if it is clear.
+    pub fn memory_recall(&self) -> T {
+        self.memory.clone().unwrap_or_else(T::zero)
+    }
+
+    /// MC: clears the memory register.
+    pub fn memory_clear(&mut self) {
+        self.memory = None;
+    }
+
+    fn update_memory(
+        &mut self,
+        value: T,
+        operation_type: OperationType,
+    ) -> Result<T, CalcError<T>> {
+        let total = operation_type.try_perform(self.memory_recall(), value)?;
+        self.memory = Some(total.clone());
+        Ok(total)
+    }
+
+    /// Lists variables in name order, followed by `ans` and `MR` once they
+    /// have a value, one `name = value` per line.
+    pub fn show_variables(&self) -> String {
+        let ans = self.ans.as_ref().map(|value| ("ans", value));
+        let memory = self.memory.as_ref().map(|value| ("MR", value));
+        self.variables()
+            .chain(ans)
+            .chain(memory)
+            .map(|(name, value)| format!("{} = {}\n", name, value.format_value()))
+            .collect()
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use super::number::CheckedNumber;
+use super::{CalcError, Calculator, Operation, OperationType};
+
+/// How a `Calculator` handles results that don't fit the number type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ArithmeticMode {
+    /// Fail with `CalcError::Overflow`.
+    #[default]
+    Checked,
+    /// Clamp to the smallest or largest representable value.
+    Saturating,
+    /// Wrap around at the boundary of the type (two's complement).
+    Wrapping,
+}
+
+impl ArithmeticMode {
+    pub fn is_checked(&self) -> bool {
+        *self == ArithmeticMode::Checked
+    }
+
+    /// How `show_history` marks a result that saturated or wrapped.
+    pub fn overflow_label(&self) -> Option<&'static str> {
+        match self {
+            ArithmeticMode::Checked => None,
+            ArithmeticMode::Saturating => Some("saturated"),
+            ArithmeticMode::Wrapping => Some("wrapped"),
+        }
+    }
+}
+
+impl OperationType {
+    /// Like `try_perform`, but an overflow is handled according to `mode`.
+    /// Returns the result and whether it saturated or wrapped.
+    ///
+    /// Division by zero and invalid exponents fail in every mode, and so
+    /// does overflow on backends without saturating or wrapping
+    /// arithmetic (floats, rationals and big integers).
+    pub fn try_perform_with<T: CheckedNumber>(
+        &self,
+        mode: ArithmeticMode,
+        x: T,
+        y: T,
+    ) -> Result<(T, bool), CalcError<T>> {
+        let err = match self.try_perform(x.clone(), y.clone()) {
+            Ok(result) => return Ok((result, false)),
+            Err(err @ CalcError::Overflow { .. }) => err,
+            Err(err) => return Err(err),
+        };
+        let result = match mode {
+            ArithmeticMode::Checked => None,
+            ArithmeticMode::Saturating => x.saturating_op(self, &y),
+            ArithmeticMode::Wrapping => x.wrapping_op(self, &y),
+        };
+        result.map(|result| (result, true)).ok_or(err)
+    }
+}
+
+impl<T: CheckedNumber> Operation<T> {
+    /// Performs the operation in `mode`, returning the history entry for
+    /// it together with the result.
+    pub(crate) fn performed(
+        first_num: T,
+        second_num: T,
+        operation_type: OperationType,
+        mode: ArithmeticMode,
+    ) -> Result<(Self, T), CalcError<T>> {
+        let (result, overflowed) =
+            operation_type.try_perform_with(mode, first_num.clone(), second_num.clone())?;
+        let mut operation =
+            Self::with_result(first_num, second_num, operation_type, result.clone());
+        operation.mode = mode;
+        operation.overflowed = overflowed;
+        Ok((operation, result))
+    }
+}
+
+impl<T: CheckedNumber> Calculator<T> {
+    /// Sets how later operations handle overflow. Entries already in
+    /// history keep the mode they were recorded with, and `repeat`
+    /// reproduces that mode.
+    pub fn set_mode(&mut self, mode: ArithmeticMode) {
+        self.mode = mode;
+    }
+
+    pub fn mode(&self) -> ArithmeticMode {
+        self.mode
+    }
+}
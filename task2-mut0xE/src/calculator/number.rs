@@ -0,0 +1,435 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use num_bigint::BigInt;
+use num_rational::Ratio;
+use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, Zero};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::OperationType;
+
+/// Exact fraction of two `i64`s, kept in lowest terms.
+pub type Rational = Ratio<i64>;
+
+/// Arithmetic a `Calculator` backend has to provide.
+///
+/// Every operation returns None instead of overflowing, wrapping or
+/// producing a value the type can't represent exactly.
+pub trait CheckedNumber:
+    Clone + PartialEq + PartialOrd + fmt::Debug + fmt::Display + FromStr
+{
+    fn zero() -> Self;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+
+    /// Whether the value may be used as an operand at all. Only floats
+    /// reject anything (NaN and infinities).
+    fn is_valid(&self) -> bool {
+        true
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+    fn checked_sub(&self, rhs: &Self) -> Option<Self>;
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+    fn checked_div(&self, rhs: &Self) -> Option<Self>;
+    fn checked_rem(&self, rhs: &Self) -> Option<Self>;
+    fn checked_neg(&self) -> Option<Self>;
+    fn checked_pow(&self, exp: u32) -> Option<Self>;
+
+    /// Converts the value to a `Power` exponent, or None if it is not a
+    /// whole number that fits in `u32`.
+    fn to_exponent(&self) -> Option<u32>;
+
+    /// How the value is written by `show_history`.
+    fn format_value(&self) -> String {
+        self.to_string()
+    }
+
+    /// Whether the value needed more precision than the backend's native
+    /// representation. Only `BigInteger` ever promotes.
+    fn is_promoted(&self) -> bool {
+        false
+    }
+
+    /// `op` with the result clamped to the type's range, for
+    /// `ArithmeticMode::Saturating`. None if the backend has no saturating
+    /// arithmetic or the operation is undefined (a zero divisor).
+    fn saturating_op(&self, _op: &OperationType, _rhs: &Self) -> Option<Self> {
+        None
+    }
+
+    /// `op` with the result wrapped around the type's range, for
+    /// `ArithmeticMode::Wrapping`. None if the backend has no wrapping
+    /// arithmetic or the operation is undefined (a zero divisor).
+    fn wrapping_op(&self, _op: &OperationType, _rhs: &Self) -> Option<Self> {
+        None
+    }
+}
+
+macro_rules! impl_checked_integer {
+    ($($t:ty),*) => {$(
+        impl CheckedNumber for $t {
+            fn zero() -> Self {
+                0
+            }
+
+            fn checked_add(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_add(*self, *rhs)
+            }
+
+            fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_sub(*self, *rhs)
+            }
+
+            fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_mul(*self, *rhs)
+            }
+
+            fn checked_div(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_div(*self, *rhs)
+            }
+
+            fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_rem(*self, *rhs)
+            }
+
+            fn checked_neg(&self) -> Option<Self> {
+                <$t>::checked_neg(*self)
+            }
+
+            fn checked_pow(&self, exp: u32) -> Option<Self> {
+                <$t>::checked_pow(*self, exp)
+            }
+
+            fn to_exponent(&self) -> Option<u32> {
+                u32::try_from(*self).ok()
+            }
+
+            fn saturating_op(&self, op: &OperationType, rhs: &Self) -> Option<Self> {
+                let (x, y) = (*self, *rhs);
+                Some(match op {
+                    OperationType::Addition => x.saturating_add(y),
+                    OperationType::Subtraction => x.saturating_sub(y),
+                    OperationType::Multiplication => x.saturating_mul(y),
+                    OperationType::Division if y == 0 => return None,
+                    OperationType::Division => x.saturating_div(y),
+                    OperationType::Remainder if y == 0 => return None,
+                    // The only overflowing remainder, MIN % -1, is exactly 0.
+                    OperationType::Remainder => x.wrapping_rem(y),
+                    OperationType::Power => x.saturating_pow(u32::try_from(y).ok()?),
+                    OperationType::Negate => (0 as $t).saturating_sub(x),
+                })
+            }
+
+            fn wrapping_op(&self, op: &OperationType, rhs: &Self) -> Option<Self> {
+                let (x, y) = (*self, *rhs);
+                Some(match op {
+                    OperationType::Addition => x.wrapping_add(y),
+                    OperationType::Subtraction => x.wrapping_sub(y),
+                    OperationType::Multiplication => x.wrapping_mul(y),
+                    OperationType::Division if y == 0 => return None,
+                    OperationType::Division => x.wrapping_div(y),
+                    OperationType::Remainder if y == 0 => return None,
+                    OperationType::Remainder => x.wrapping_rem(y),
+                    OperationType::Power => x.wrapping_pow(u32::try_from(y).ok()?),
+                    OperationType::Negate => x.wrapping_neg(),
+                })
+            }
+        }
+    )*};
+}
+
+impl_checked_integer!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+
+/// Floats treat any non-finite result as an overflow, so history never
+/// contains NaN or infinities.
+impl CheckedNumber for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+
+    fn is_valid(&self) -> bool {
+        self.is_finite()
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        finite(self + rhs)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        finite(self - rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        finite(self * rhs)
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        finite(self / rhs)
+    }
+
+    fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+        finite(self % rhs)
+    }
+
+    fn checked_neg(&self) -> Option<Self> {
+        finite(-self)
+    }
+
+    fn checked_pow(&self, exp: u32) -> Option<Self> {
+        finite(self.powf(exp.into()))
+    }
+
+    fn to_exponent(&self) -> Option<u32> {
+        let in_range = *self >= 0.0 && *self <= u32::MAX.into();
+        (in_range && self.fract() == 0.0).then_some(*self as u32)
+    }
+
+    // Debug keeps the decimal point on whole numbers, so `5.0` isn't
+    // mistaken for an integer result.
+    fn format_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+fn finite(value: f64) -> Option<f64> {
+    value.is_finite().then_some(value)
+}
+
+impl CheckedNumber for Rational {
+    fn zero() -> Self {
+        Zero::zero()
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        CheckedAdd::checked_add(self, rhs)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        CheckedSub::checked_sub(self, rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        CheckedMul::checked_mul(self, rhs)
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        CheckedDiv::checked_div(self, rhs)
+    }
+
+    // Truncated remainder, matching the sign convention of the integers:
+    // a % b = a - b * trunc(a / b)
+    fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+        let quotient = CheckedDiv::checked_div(self, rhs)?.trunc();
+        let product = CheckedMul::checked_mul(rhs, &quotient)?;
+        CheckedSub::checked_sub(self, &product)
+    }
+
+    fn checked_neg(&self) -> Option<Self> {
+        Some(Ratio::new_raw(self.numer().checked_neg()?, *self.denom()))
+    }
+
+    fn checked_pow(&self, exp: u32) -> Option<Self> {
+        num_traits::checked_pow(*self, usize::try_from(exp).ok()?)
+    }
+
+    fn to_exponent(&self) -> Option<u32> {
+        if !self.is_integer() {
+            return None;
+        }
+        u32::try_from(self.to_integer()).ok()
+    }
+}
+
+/// Results larger than this many bits are treated as overflow, so a
+/// stray `10 ^ 4000000000` can't exhaust memory.
+const MAX_BIG_INTEGER_BITS: u64 = 1 << 20;
+
+/// An `i64` that is promoted to an arbitrary-precision integer instead of
+/// overflowing.
+///
+/// Values are always kept in canonical form: anything that fits in `i64`
+/// is `Small`, so two equal numbers compare equal regardless of how they
+/// were computed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BigInteger {
+    Small(i64),
+    Big(BigInt),
+}
+
+impl BigInteger {
+    fn from_big(value: BigInt) -> Self {
+        match i64::try_from(&value) {
+            Ok(small) => BigInteger::Small(small),
+            Err(_) => BigInteger::Big(value),
+        }
+    }
+
+    fn to_big(&self) -> BigInt {
+        match self {
+            BigInteger::Small(value) => BigInt::from(*value),
+            BigInteger::Big(value) => value.clone(),
+        }
+    }
+
+    /// Tries `small` on two `i64` operands first and falls back to `big`
+    /// when either operand is already big or the `i64` operation overflows.
+    fn promote_with(
+        &self,
+        rhs: &Self,
+        small: fn(i64, i64) -> Option<i64>,
+        big: fn(BigInt, BigInt) -> BigInt,
+    ) -> Self {
+        if let (BigInteger::Small(x), BigInteger::Small(y)) = (self, rhs) {
+            if let Some(value) = small(*x, *y) {
+                return BigInteger::Small(value);
+            }
+        }
+        Self::from_big(big(self.to_big(), rhs.to_big()))
+    }
+}
+
+impl Ord for BigInteger {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (BigInteger::Small(x), BigInteger::Small(y)) => x.cmp(y),
+            _ => self.to_big().cmp(&other.to_big()),
+        }
+    }
+}
+
+impl PartialOrd for BigInteger {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<i64> for BigInteger {
+    fn from(value: i64) -> Self {
+        BigInteger::Small(value)
+    }
+}
+
+impl From<BigInt> for BigInteger {
+    fn from(value: BigInt) -> Self {
+        Self::from_big(value)
+    }
+}
+
+impl fmt::Display for BigInteger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BigInteger::Small(value) => value.fmt(f),
+            BigInteger::Big(value) => value.fmt(f),
+        }
+    }
+}
+
+impl FromStr for BigInteger {
+    type Err = num_bigint::ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<i64>() {
+            Ok(value) => Ok(BigInteger::Small(value)),
+            Err(_) => s.parse::<BigInt>().map(Self::from_big),
+        }
+    }
+}
+
+// Small values are written as JSON numbers and big ones as strings, since
+// most JSON readers can't hold integers beyond 64 bits.
+impl Serialize for BigInteger {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            BigInteger::Small(value) => serializer.serialize_i64(*value),
+            BigInteger::Big(value) => serializer.collect_str(value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BigInteger {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(i64),
+            Text(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(value) => Ok(BigInteger::Small(value)),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl CheckedNumber for BigInteger {
+    fn zero() -> Self {
+        BigInteger::Small(0)
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self.promote_with(rhs, i64::checked_add, |x, y| x + y))
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        Some(self.promote_with(rhs, i64::checked_sub, |x, y| x - y))
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self.promote_with(rhs, i64::checked_mul, |x, y| x * y))
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        if rhs.is_zero() {
+            return None;
+        }
+        Some(self.promote_with(rhs, i64::checked_div, |x, y| x / y))
+    }
+
+    fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+        if rhs.is_zero() {
+            return None;
+        }
+        // `i64::MIN % -1` only overflows as a side effect of the division;
+        // the remainder itself is 0, so it needs no promotion.
+        Some(self.promote_with(rhs, |x, y| Some(x.wrapping_rem(y)), |x, y| x % y))
+    }
+
+    fn checked_neg(&self) -> Option<Self> {
+        match self {
+            BigInteger::Small(value) => match value.checked_neg() {
+                Some(negated) => Some(BigInteger::Small(negated)),
+                None => Some(Self::from_big(-BigInt::from(*value))),
+            },
+            BigInteger::Big(value) => Some(Self::from_big(-value)),
+        }
+    }
+
+    fn checked_pow(&self, exp: u32) -> Option<Self> {
+        if let BigInteger::Small(value) = self {
+            if let Some(result) = value.checked_pow(exp) {
+                return Some(BigInteger::Small(result));
+            }
+        }
+        let base = self.to_big();
+        if base.bits().saturating_mul(exp.into()) > MAX_BIG_INTEGER_BITS {
+            return None;
+        }
+        Some(Self::from_big(base.pow(exp)))
+    }
+
+    fn to_exponent(&self) -> Option<u32> {
+        match self {
+            BigInteger::Small(value) => u32::try_from(*value).ok(),
+            BigInteger::Big(_) => None,
+        }
+    }
+
+    fn is_promoted(&self) -> bool {
+        matches!(self, BigInteger::Big(_))
+    }
+}
@@ -0,0 +1,388 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::bounded::EvictionPolicy;
+use super::number::CheckedNumber;
+use super::{ArithmeticMode, Calculator, Operation, OperationType};
+
+const CSV_HEADER: &str = "index,lhs,op,rhs,result";
+
+/// On-disk formats supported by `Calculator::save_history`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryFormat {
+    /// A JSON object with every field of each entry, along with the index
+    /// of the first one and the capacity and eviction policy. A bare array
+    /// of entries is read as an unbounded history starting at index 0.
+    Json,
+    /// One `index,lhs,op,rhs,result` row per entry. Ids, repeat links,
+    /// timestamps and the capacity are not kept, and the arithmetic mode is
+    /// only recovered for results that saturated or wrapped.
+    Csv,
+}
+
+/// The JSON form of a history: its entries, as `H`, and how they are
+/// indexed and bounded.
+#[derive(Serialize, Deserialize)]
+struct SavedHistory<H> {
+    #[serde(default)]
+    first_index: usize,
+    #[serde(default)]
+    capacity: Option<usize>,
+    #[serde(default)]
+    eviction_policy: EvictionPolicy,
+    history: H,
+}
+
+type LoadedHistory<T> = SavedHistory<Vec<Operation<T>>>;
+
+/// Errors returned when saving or loading history.
+///
+/// A `row` is a line number in CSV, counting the header as line 1, and the
+/// position of the entry in the array in JSON.
+#[derive(Debug)]
+pub enum PersistError<T = i64> {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// A CSV row or JSON entry that does not have the expected shape.
+    Malformed {
+        row: usize,
+        message: String,
+    },
+    /// The recorded result of an entry differs from what
+    /// `OperationType::try_perform_with` computes for its operands.
+    ResultMismatch {
+        row: usize,
+        recorded: Option<T>,
+        computed: Option<T>,
+    },
+}
+
+impl<T: CheckedNumber> fmt::Display for PersistError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::Io(err) => write!(f, "i/o error: {}", err),
+            PersistError::Json(err) => write!(f, "invalid json: {}", err),
+            PersistError::Malformed { row, message } => {
+                write!(f, "malformed row {}: {}", row, message)
+            }
+            PersistError::ResultMismatch {
+                row,
+                recorded,
+                computed,
+            } => write!(
+                f,
+                "row {} records result {:?} but the operation yields {:?}",
+                row, recorded, computed
+            ),
+        }
+    }
+}
+
+impl<T: CheckedNumber> std::error::Error for PersistError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PersistError::Io(err) => Some(err),
+            PersistError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl<T> From<io::Error> for PersistError<T> {
+    fn from(err: io::Error) -> Self {
+        PersistError::Io(err)
+    }
+}
+
+impl<T> From<serde_json::Error> for PersistError<T> {
+    fn from(err: serde_json::Error) -> Self {
+        PersistError::Json(err)
+    }
+}
+
+impl<T: CheckedNumber + Serialize + DeserializeOwned> Calculator<T> {
+    /// Writes the current history to `writer` in the given format.
+    pub fn save_history<W: Write>(
+        &self,
+        mut writer: W,
+        format: HistoryFormat,
+    ) -> Result<(), PersistError<T>> {
+        match format {
+            HistoryFormat::Json => {
+                let saved = SavedHistory {
+                    first_index: self.first_index,
+                    capacity: self.capacity,
+                    eviction_policy: self.eviction_policy,
+                    history: &self.history,
+                };
+                serde_json::to_writer_pretty(&mut writer, &saved)?
+            }
+            HistoryFormat::Csv => {
+                writeln!(writer, "{}", CSV_HEADER)?;
+                for (index, op) in (self.first_index..).zip(&self.history) {
+                    let rhs = if op.operation_type.is_unary() {
+                        String::new()
+                    } else {
+                        op.second_num.to_string()
+                    };
+                    let result = op
+                        .result
+                        .as_ref()
+                        .map(|r| r.to_string())
+                        .unwrap_or_default();
+                    writeln!(
+                        writer,
+                        "{},{},{},{},{}",
+                        index,
+                        op.first_num,
+                        op.operation_type.get_sign(),
+                        rhs,
+                        result
+                    )?;
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads history written by `save_history` into a new calculator.
+    /// Indexes are kept, so a history saved after older entries were
+    /// evicted still starts from the first index it was saved with. A JSON
+    /// history also gets back its capacity and eviction policy.
+    ///
+    /// Every entry is checked against `OperationType::try_perform_with` in
+    /// its recorded mode, so a file whose results were edited by hand is
+    /// rejected rather than loaded.
+    pub fn load_history<R: Read>(
+        reader: R,
+        format: HistoryFormat,
+    ) -> Result<Self, PersistError<T>> {
+        let (saved, next_id) = match format {
+            HistoryFormat::Json => read_json(reader)?,
+            HistoryFormat::Csv => {
+                let (first_index, history) = read_csv(reader)?;
+                // CSV ids are the row positions.
+                let next_id = history.len() as u64;
+                (SavedHistory::unbounded(first_index, history), next_id)
+            }
+        };
+
+        Ok(Calculator {
+            next_id,
+            history: saved.history,
+            first_index: saved.first_index,
+            capacity: saved.capacity,
+            eviction_policy: saved.eviction_policy,
+            ..Calculator::default()
+        })
+    }
+
+    /// Replaces the history with one read by `load_history`, keeping the
+    /// rest of the session: variables, the memory register, `ans`, the
+    /// arithmetic mode and the capacity. Undo and redo start afresh, since
+    /// their steps refer to the old history.
+    ///
+    /// On error the calculator is left unchanged.
+    pub fn replace_history<R: Read>(
+        &mut self,
+        reader: R,
+        format: HistoryFormat,
+    ) -> Result<(), PersistError<T>> {
+        let loaded = Self::load_history(reader, format)?;
+        self.history = loaded.history;
+        self.first_index = loaded.first_index;
+        self.next_id = loaded.next_id;
+        self.evict_excess();
+        self.undo_stack.clear();
+        Ok(())
+    }
+}
+
+impl<H> SavedHistory<H> {
+    fn unbounded(first_index: usize, history: H) -> Self {
+        Self {
+            first_index,
+            capacity: None,
+            eviction_policy: EvictionPolicy::default(),
+            history,
+        }
+    }
+}
+
+/// Reads a JSON history, returning it along with the id for the next entry.
+/// A bare array of entries is an unbounded history starting at index 0.
+fn read_json<T: CheckedNumber + DeserializeOwned, R: Read>(
+    reader: R,
+) -> Result<(LoadedHistory<T>, u64), PersistError<T>> {
+    let value: serde_json::Value = serde_json::from_reader(reader)?;
+    let mut saved: LoadedHistory<T> = if value.is_array() {
+        SavedHistory::unbounded(0, serde_json::from_value(value)?)
+    } else {
+        serde_json::from_value(value)?
+    };
+    let history = &mut saved.history;
+
+    if saved.first_index.checked_add(history.len()).is_none() {
+        return Err(PersistError::Malformed {
+            row: 0,
+            message: format!("first index {} is too large", saved.first_index),
+        });
+    }
+    if let Some(capacity) = saved.capacity.filter(|&c| history.len() > c) {
+        return Err(PersistError::Malformed {
+            row: capacity,
+            message: format!("history is longer than its capacity of {}", capacity),
+        });
+    }
+    for (row, pair) in history.windows(2).enumerate() {
+        if pair[1].id <= pair[0].id {
+            return Err(PersistError::Malformed {
+                row: row + 1,
+                message: format!("id {} is not increasing", pair[1].id),
+            });
+        }
+    }
+    for (row, op) in history.iter_mut().enumerate() {
+        check_result(op, row)?;
+    }
+    let next_id = match history.last() {
+        Some(last) => last
+            .id
+            .checked_add(1)
+            .ok_or_else(|| PersistError::Malformed {
+                row: history.len() - 1,
+                message: format!("id {} leaves no id for new entries", last.id),
+            })?,
+        None => 0,
+    };
+    Ok((saved, next_id))
+}
+
+/// Reads CSV rows, returning the index of the first one along with the
+/// entries. Indexes may start anywhere but must then count up by one.
+fn read_csv<T: CheckedNumber, R: Read>(
+    reader: R,
+) -> Result<(usize, Vec<Operation<T>>), PersistError<T>> {
+    let mut lines = (1..).zip(BufReader::new(reader).lines());
+    match lines.next().map(|(_, header)| header).transpose()? {
+        Some(header) if header.trim() == CSV_HEADER => {}
+        _ => {
+            return Err(PersistError::Malformed {
+                row: 1,
+                message: format!("expected header {:?}", CSV_HEADER),
+            })
+        }
+    }
+
+    let mut first_index = None;
+    let mut history = Vec::new();
+    for (number, line) in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row = history.len();
+        let malformed = |message: String| PersistError::Malformed {
+            row: number,
+            message,
+        };
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [index, lhs, op, rhs, result] = fields[..] else {
+            return Err(malformed(format!(
+                "expected 5 fields, found {}",
+                fields.len()
+            )));
+        };
+
+        // The index after the last entry must exist too, so `usize::MAX`
+        // itself is out of range.
+        let index: usize = index
+            .parse()
+            .ok()
+            .filter(|&index| index < usize::MAX)
+            .ok_or_else(|| malformed(format!("invalid index {:?}", index)))?;
+        let first = *first_index.get_or_insert(index);
+        if index != first + row {
+            return Err(malformed(format!(
+                "expected index {}, found {}",
+                first + row,
+                index
+            )));
+        }
+        let operation_type = OperationType::from_sign(op)
+            .ok_or_else(|| malformed(format!("unknown operation {:?}", op)))?;
+        let parse_num = |field: &str| {
+            field
+                .parse::<T>()
+                .map_err(|_| malformed(format!("invalid number {:?}", field)))
+        };
+        let first_num = parse_num(lhs)?;
+        let second_num = if operation_type.is_unary() && rhs.is_empty() {
+            T::zero()
+        } else {
+            parse_num(rhs)?
+        };
+        let result = if result.is_empty() {
+            None
+        } else {
+            Some(parse_num(result)?)
+        };
+
+        let mut operation = Operation {
+            mode: infer_mode(&operation_type, &first_num, &second_num, result.as_ref()),
+            result,
+            id: row as u64,
+            ..Operation::new(first_num, second_num, operation_type)
+        };
+        check_result(&mut operation, number)?;
+        history.push(operation);
+    }
+    Ok((first_index.unwrap_or(0), history))
+}
+
+/// Checks that `op` records the result `OperationType::try_perform_with`
+/// computes in its mode, and sets whether it overflowed or was promoted
+/// from the operands rather than trusting the file.
+fn check_result<T: CheckedNumber>(
+    op: &mut Operation<T>,
+    row: usize,
+) -> Result<(), PersistError<T>> {
+    let computed = op
+        .operation_type
+        .try_perform_with(op.mode, op.first_num.clone(), op.second_num.clone())
+        .ok();
+    match computed {
+        Some((result, overflowed)) if op.result.as_ref() == Some(&result) => {
+            op.overflowed = overflowed;
+            op.promoted = Operation::is_promoted(&op.first_num, &op.second_num, Some(&result));
+            Ok(())
+        }
+        _ => Err(PersistError::ResultMismatch {
+            row,
+            recorded: op.result.clone(),
+            computed: computed.map(|(result, _)| result),
+        }),
+    }
+}
+
+/// CSV has no mode column, so an entry's mode is taken to be the one that
+/// reproduces its recorded result, preferring `Checked`.
+fn infer_mode<T: CheckedNumber>(
+    operation_type: &OperationType,
+    x: &T,
+    y: &T,
+    result: Option<&T>,
+) -> ArithmeticMode {
+    [ArithmeticMode::Saturating, ArithmeticMode::Wrapping]
+        .into_iter()
+        .find(|&mode| {
+            let computed = operation_type.try_perform_with(mode, x.clone(), y.clone());
+            matches!(computed, Ok((value, true)) if Some(&value) == result)
+        })
+        .unwrap_or_default()
+}
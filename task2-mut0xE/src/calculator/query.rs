@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::iter::Enumerate;
+use std::ops::{Bound, RangeBounds};
+use std::slice;
+
+use super::number::CheckedNumber;
+use super::{Calculator, Operation, OperationType};
+
+/// An iterator over history entries and their indexes, narrowed down by
+/// any combination of filters. Created by `Calculator::query`.
+#[derive(Debug, Clone)]
+pub struct HistoryQuery<'a, T> {
+    entries: Enumerate<slice::Iter<'a, Operation<T>>>,
+    first_index: usize,
+    operation_type: Option<OperationType>,
+    result_range: Option<(Bound<T>, Bound<T>)>,
+    operand: Option<T>,
+}
+
+impl<T: CheckedNumber> HistoryQuery<'_, T> {
+    /// Keeps only entries of the given operation type.
+    pub fn operation(mut self, operation_type: OperationType) -> Self {
+        self.operation_type = Some(operation_type);
+        self
+    }
+
+    /// Keeps only entries whose result lies in `range`. Entries without a
+    /// result never match.
+    pub fn result_in<R: RangeBounds<T>>(mut self, range: R) -> Self {
+        self.result_range = Some((range.start_bound().cloned(), range.end_bound().cloned()));
+        self
+    }
+
+    /// Keeps only entries that use `value` as either operand. The ignored
+    /// second operand of unary operations does not count.
+    pub fn operand(mut self, value: T) -> Self {
+        self.operand = Some(value);
+        self
+    }
+
+    /// Aggregates the remaining matching entries.
+    pub fn stats(self) -> HistoryStats<T> {
+        let mut stats = HistoryStats {
+            count: 0,
+            per_operation: HashMap::new(),
+            sum: None,
+            min: None,
+            max: None,
+        };
+        let mut sum = Some(T::zero());
+        for (_, entry) in self {
+            stats.count += 1;
+            *stats
+                .per_operation
+                .entry(entry.operation_type.clone())
+                .or_default() += 1;
+            let Some(result) = &entry.result else {
+                continue;
+            };
+            sum = sum.and_then(|sum| sum.checked_add(result));
+            if stats.min.as_ref().is_none_or(|min| result < min) {
+                stats.min = Some(result.clone());
+            }
+            if stats.max.as_ref().is_none_or(|max| result > max) {
+                stats.max = Some(result.clone());
+            }
+        }
+        if stats.min.is_some() {
+            stats.sum = sum;
+        }
+        stats
+    }
+
+    fn matches(&self, entry: &Operation<T>) -> bool {
+        if let Some(operation_type) = &self.operation_type {
+            if entry.operation_type != *operation_type {
+                return false;
+            }
+        }
+        if let Some(range) = &self.result_range {
+            if !entry.result.as_ref().is_some_and(|r| range.contains(r)) {
+                return false;
+            }
+        }
+        if let Some(value) = &self.operand {
+            let uses_rhs = !entry.operation_type.is_unary() && entry.second_num == *value;
+            if entry.first_num != *value && !uses_rhs {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<'a, T: CheckedNumber> Iterator for HistoryQuery<'a, T> {
+    type Item = (usize, &'a Operation<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (index, entry) = self.entries.next()?;
+            if self.matches(entry) {
+                return Some((self.first_index + index, entry));
+            }
+        }
+    }
+}
+
+/// Aggregate figures over a set of history entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryStats<T = i64> {
+    /// Number of entries, including ones without a result.
+    pub count: usize,
+    /// Number of entries of each operation type that occurs.
+    pub per_operation: HashMap<OperationType, usize>,
+    /// Sum of all results, or None if there are none or the sum overflows.
+    pub sum: Option<T>,
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T> HistoryStats<T> {
+    /// How many entries had the given operation type.
+    pub fn count_of(&self, operation_type: &OperationType) -> usize {
+        self.per_operation.get(operation_type).copied().unwrap_or(0)
+    }
+}
+
+impl<T: CheckedNumber> Calculator<T> {
+    /// Iterates over every history entry with its index. Chain the
+    /// `HistoryQuery` filters to narrow it down.
+    pub fn query(&self) -> HistoryQuery<'_, T> {
+        HistoryQuery {
+            entries: self.history.iter().enumerate(),
+            first_index: self.first_index(),
+            operation_type: None,
+            result_range: None,
+            operand: None,
+        }
+    }
+
+    /// Statistics over the whole history; see `HistoryQuery::stats` for a
+    /// filtered subset.
+    pub fn statistics(&self) -> HistoryStats<T> {
+        self.query().stats()
+    }
+}
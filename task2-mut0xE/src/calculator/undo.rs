@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+
+use super::number::CheckedNumber;
+use super::{Calculator, Operation};
+
+const DEFAULT_MAX_UNDO_DEPTH: usize = 100;
+
+/// A single undoable change to history, holding the entries it affected.
+#[derive(Debug, Clone)]
+pub(super) enum Edit<T> {
+    /// Entries appended to the end of history by one calculator call, the
+    /// index of the first one and the `ans` they replaced.
+    Append {
+        entries: Vec<Operation<T>>,
+        first_index: usize,
+        previous_ans: Option<T>,
+    },
+    /// Entries removed by `clear_history`, and the index of the first one.
+    Clear {
+        entries: Vec<Operation<T>>,
+        first_index: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct UndoStack<T> {
+    undo: VecDeque<Edit<T>>,
+    redo: Vec<Edit<T>>,
+    max_depth: usize,
+}
+
+impl<T> UndoStack<T> {
+    pub(super) fn new() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_depth: DEFAULT_MAX_UNDO_DEPTH,
+        }
+    }
+
+    /// Records a fresh edit. Anything that was undone can no longer be redone.
+    pub(super) fn push(&mut self, edit: Edit<T>) {
+        self.redo.clear();
+        self.push_undo(edit);
+    }
+
+    /// Forgets every edit, for when history is replaced wholesale.
+    pub(super) fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn push_undo(&mut self, edit: Edit<T>) {
+        if self.max_depth == 0 {
+            return;
+        }
+        if self.undo.len() == self.max_depth {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+    }
+}
+
+impl<T: CheckedNumber> Calculator<T> {
+    /// Sets how many steps `undo` can go back. The oldest steps are
+    /// forgotten once the limit is reached; zero disables undo entirely.
+    pub fn set_max_undo_depth(&mut self, depth: usize) {
+        let stack = &mut self.undo_stack;
+        stack.max_depth = depth;
+        while stack.undo.len() > depth {
+            stack.undo.pop_front();
+        }
+    }
+
+    /// Takes back the most recent change to history: the entries appended
+    /// by one operation, `repeat`, `repeat_range`, `batch` or `evaluate`
+    /// call, or a whole `clear_history`. Taking back entries also restores
+    /// the `ans` from before them. Returns false if there is nothing
+    /// to undo. Entries evicted from a bounded history are not restored,
+    /// and once all the entries of a change have been evicted, neither it
+    /// nor any change before it can be undone.
+    pub fn undo(&mut self) -> bool {
+        let Some(edit) = self.undo_stack.undo.pop_back() else {
+            return false;
+        };
+        match &edit {
+            Edit::Append {
+                entries,
+                first_index,
+                previous_ans,
+            } => {
+                if first_index + entries.len() <= self.first_index {
+                    // Every older change is at least as stale.
+                    self.undo_stack.undo.clear();
+                    return false;
+                }
+                let len = first_index.saturating_sub(self.first_index);
+                self.history.truncate(len);
+                self.ans = previous_ans.clone();
+            }
+            Edit::Clear {
+                entries,
+                first_index,
+            } => {
+                self.history = entries.clone();
+                self.first_index = *first_index;
+                self.evict_excess();
+            }
+        }
+        self.undo_stack.redo.push(edit);
+        true
+    }
+
+    /// Re-applies the most recently undone change. Returns false if there
+    /// is nothing to redo, or if the entries no longer fit in a full
+    /// history with the `RejectNew` policy. Entries keep their indexes, so
+    /// those that had been evicted before the undo stay evicted.
+    pub fn redo(&mut self) -> bool {
+        let Some(edit) = self.undo_stack.redo.pop() else {
+            return false;
+        };
+        match &edit {
+            Edit::Append {
+                entries,
+                first_index,
+                ..
+            } => {
+                let evicted = self.first_index.saturating_sub(*first_index);
+                let kept = &entries[evicted.min(entries.len())..];
+                if self.check_room(kept.len()).is_err() {
+                    self.undo_stack.redo.push(edit);
+                    return false;
+                }
+                self.history.extend(kept.iter().cloned());
+                self.ans = entries.last().and_then(|op| op.result.clone());
+                self.evict_excess();
+            }
+            Edit::Clear { .. } => {
+                self.history.clear();
+                self.first_index = 0;
+            }
+        }
+        self.undo_stack.push_undo(edit);
+        true
+    }
+}
@@ -2,6 +2,7 @@
 #![allow(unused_doc_comments)]
 
 mod calculator;
+mod repl;
 mod shapes;
 ///-------------------------------------------------------------------------------
 ///
@@ -13,7 +14,11 @@ mod shapes;
 ///-------------------------------------------------------------------------------
 mod tests;
 use calculator::*;
+use repl::Repl;
 use shapes::*;
+use std::fs::File;
+use std::io::{self, BufReader, IsTerminal};
+use std::process::ExitCode;
 
 fn rectangle_example() {
     let width_in: f64 = 7.0;
@@ -85,10 +90,36 @@ fn calculator_example() {
     assert_eq!(calculator.show_history(), "");
 }
 
-fn main() {
+/// `task2 repl [SCRIPT]` starts the calculator REPL, reading from SCRIPT
+/// if given and from stdin otherwise.
+fn repl(script: Option<&str>) -> io::Result<()> {
+    let mut repl = Repl::new();
+    let stdout = io::stdout().lock();
+    match script {
+        Some(path) => repl.run(BufReader::new(File::open(path)?), stdout, false),
+        None => {
+            let interactive = io::stdin().is_terminal();
+            repl.run(io::stdin().lock(), stdout, interactive)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("repl") {
+        return match repl(args.get(1).map(String::as_str)) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
     calculator_example();
     rectangle_example();
     circle_example();
 
     println!("All examples completed successfully!");
+    ExitCode::SUCCESS
 }
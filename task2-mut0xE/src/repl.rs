@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::calculator::persist::HistoryFormat;
+use crate::calculator::Calculator;
+
+const HELP: &str = "\
+Enter an arithmetic expression such as (3 + 4) * -2 or let x = ans / 2,
+or one of:
+  :history      show the calculation history
+  :vars         show variables, ans and the memory register MR
+  :m+ [EXPR]    add EXPR (or ans) to the memory register
+  :m- [EXPR]    subtract EXPR (or ans) from the memory register
+  :mc           clear the memory register
+  :repeat N     repeat history entry N
+  :undo         take back the last change
+  :redo         re-apply the last undone change
+  :clear        clear the history
+  :save FILE    save the history (CSV if FILE ends in .csv, JSON otherwise)
+  :load FILE    replace the history with one saved by :save, keeping
+                variables and memory; this can't be undone
+  :help         show this message
+  :quit         leave the REPL";
+
+/// A line-oriented calculator session.
+///
+/// Each input line produces its output immediately, so the same session can
+/// be driven interactively or replayed from a script file.
+pub struct Repl {
+    calculator: Calculator,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self {
+            calculator: Calculator::new(),
+        }
+    }
+
+    /// Reads lines from `input` until it ends or `:quit` is entered, writing
+    /// the response to each line to `output`. A prompt is printed before
+    /// each line when `interactive` is set.
+    pub fn run<R: BufRead, W: Write>(
+        &mut self,
+        input: R,
+        mut output: W,
+        interactive: bool,
+    ) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            if interactive {
+                write!(output, "> ")?;
+                output.flush()?;
+            }
+            let Some(line) = lines.next().transpose()? else {
+                break;
+            };
+            let line = line.trim();
+            if line == ":quit" {
+                break;
+            }
+            if let Some(response) = self.handle_line(line) {
+                writeln!(output, "{}", response)?;
+            }
+        }
+        output.flush()
+    }
+
+    /// Runs a single line and returns what should be printed for it.
+    /// Blank lines and `#` comments produce no output.
+    pub fn handle_line(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let response = match line.strip_prefix(':') {
+            Some(command) => self.handle_command(command),
+            None => self
+                .calculator
+                .evaluate(line)
+                .map(|result| result.to_string())
+                .map_err(|err| err.to_string()),
+        };
+        Some(response.unwrap_or_else(|err| format!("error: {}", err)))
+    }
+
+    fn handle_command(&mut self, command: &str) -> Result<String, String> {
+        let (name, arg) = match command.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (command, ""),
+        };
+        match (name, arg) {
+            ("help", "") => Ok(HELP.to_string()),
+            ("history", "") if self.calculator.history.is_empty() => {
+                Ok("history is empty".to_string())
+            }
+            ("history", "") => Ok(self.calculator.show_history().trim_end().to_string()),
+            ("vars", "") => {
+                let variables = self.calculator.show_variables();
+                if variables.is_empty() {
+                    Ok("no variables".to_string())
+                } else {
+                    Ok(variables.trim_end().to_string())
+                }
+            }
+            ("m+" | "m-", expr) => {
+                let value = if expr.is_empty() {
+                    self.calculator.value_of("ans")
+                } else {
+                    self.calculator.evaluate(expr)
+                };
+                let total = value.and_then(|value| {
+                    if name == "m+" {
+                        self.calculator.memory_add(value)
+                    } else {
+                        self.calculator.memory_subtract(value)
+                    }
+                });
+                total
+                    .map(|total| format!("MR = {}", total))
+                    .map_err(|err| err.to_string())
+            }
+            ("mc", "") => {
+                self.calculator.memory_clear();
+                Ok("memory cleared".to_string())
+            }
+            ("clear", "") => {
+                self.calculator.clear_history();
+                Ok("history cleared".to_string())
+            }
+            ("undo", "") => Ok(if self.calculator.undo() {
+                "undone".to_string()
+            } else {
+                "nothing to undo".to_string()
+            }),
+            ("redo", "") => Ok(if self.calculator.redo() {
+                "redone".to_string()
+            } else {
+                "nothing to redo".to_string()
+            }),
+            ("repeat", index) if !index.is_empty() => {
+                let index = index
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid history index {:?}", index))?;
+                self.calculator
+                    .try_repeat(index)
+                    .map(|result| result.to_string())
+                    .map_err(|err| err.to_string())
+            }
+            ("save", path) if !path.is_empty() => {
+                let file = File::create(path).map_err(|err| err.to_string())?;
+                self.calculator
+                    .save_history(BufWriter::new(file), format_for(path))
+                    .map_err(|err| err.to_string())?;
+                Ok(format!(
+                    "saved {} entries to {}",
+                    self.calculator.history.len(),
+                    path
+                ))
+            }
+            ("load", path) if !path.is_empty() => {
+                let file = File::open(path).map_err(|err| err.to_string())?;
+                self.calculator
+                    .replace_history(BufReader::new(file), format_for(path))
+                    .map_err(|err| err.to_string())?;
+                Ok(format!(
+                    "loaded {} entries from {}",
+                    self.calculator.history.len(),
+                    path
+                ))
+            }
+            ("repeat" | "save" | "load", _) => Err(format!(":{} needs an argument", name)),
+            _ => Err(format!("unknown command :{} (try :help)", name)),
+        }
+    }
+}
+
+fn format_for(path: &str) -> HistoryFormat {
+    match Path::new(path).extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => HistoryFormat::Csv,
+        _ => HistoryFormat::Json,
+    }
+}
@@ -1,64 +1,344 @@
-use std::f64::consts::PI;
-///-------------------------------------------------------------------------------
-///
-/// This is your first task to get warmed up and see how useful traits can be.
-///
-/// Complete the implementation of methods in the Rectangle and Circle structs,
-/// then implement the Shape trait for both structs.
-///
-/// Tasks:
-/// 1. Implement Rectangle struct methods (constructor, setters, getters)
-/// 2. Implement Circle struct methods (constructor, setter, getter)
-/// 3. Implement the Shape trait for both Rectangle and Circle
-/// 4. Handle validation errors properly using the Error enum
-///
-///-------------------------------------------------------------------------------
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+pub mod collision;
+pub mod serial;
+pub mod solid;
+pub mod svg;
+pub mod transform;
+pub mod units;
+
+use collision::Geometry;
+//-------------------------------------------------------------------------------
+//
+// This is your first task to get warmed up and see how useful traits can be.
+//
+// Complete the implementation of methods in the Rectangle and Circle structs,
+// then implement the Shape trait for both structs.
+//
+// Tasks:
+// 1. Implement Rectangle struct methods (constructor, setters, getters)
+// 2. Implement Circle struct methods (constructor, setter, getter)
+// 3. Implement the Shape trait for both Rectangle and Circle
+// 4. Handle validation errors properly using the Error enum
+//
+//-------------------------------------------------------------------------------
 
 pub trait Shape {
     fn area(&self) -> f64;
     fn perimeter(&self) -> f64;
+    /// The point the shape is placed at; see each shape for which point of
+    /// the shape that is. Shapes start out at the origin.
+    fn position(&self) -> Point;
+    /// The smallest axis-aligned box containing the shape.
+    fn bounding_box(&self) -> BoundingBox;
+    /// The centre of mass of the shape's area.
+    fn centroid(&self) -> Point;
+    /// Whether `point` lies inside the shape or on its boundary.
+    fn contains(&self, point: Point) -> bool;
+    /// The distance from `point` to the nearest point of the shape, which
+    /// is zero for points the shape contains.
+    fn distance_to(&self, point: Point) -> f64;
+    /// The outline of the shape where it is placed, which collision
+    /// queries work on.
+    fn geometry(&self) -> Geometry;
+
+    /// Whether the shapes overlap or touch.
+    fn intersects(&self, other: &dyn Shape) -> bool {
+        collision::intersects(&self.geometry(), &other.geometry())
+    }
+    /// The area covered by both shapes. Fails with `UnsupportedIntersection`
+    /// for an ellipse against a circle or ellipse of a different aspect
+    /// ratio, which has no closed form.
+    fn intersection_area(&self, other: &dyn Shape) -> Result<f64, Error> {
+        collision::intersection_area(&self.geometry(), &other.geometry())
+    }
 }
 
+/// An axis-aligned rectangle, positioned by its lower-left corner.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "serial::ShapeRecord", try_from = "serial::ShapeRecord")]
 pub struct Rectangle {
     width: f64,
     height: f64,
+    position: Point,
+    zero_policy: ZeroPolicy,
 }
 
+/// A circle, positioned by its centre.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "serial::ShapeRecord", try_from = "serial::ShapeRecord")]
 pub struct Circle {
     radius: f64,
+    position: Point,
+    zero_policy: ZeroPolicy,
+}
+
+/// An axis-aligned square with sides of length `side`, positioned by its
+/// lower-left corner.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "serial::ShapeRecord", try_from = "serial::ShapeRecord")]
+pub struct Square {
+    side: f64,
+    position: Point,
+    zero_policy: ZeroPolicy,
+}
+
+/// A triangle given by the lengths of its three sides. It is positioned by
+/// the vertex between sides `b` and `c`; side `c` runs from there along the
+/// x axis and the third vertex lies above it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "serial::ShapeRecord", try_from = "serial::ShapeRecord")]
+pub struct Triangle {
+    a: f64,
+    b: f64,
+    c: f64,
+    position: Point,
+    zero_policy: ZeroPolicy,
+}
+
+/// An axis-aligned ellipse with horizontal radius `radius_x` and vertical
+/// radius `radius_y`, positioned by its centre.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "serial::ShapeRecord", try_from = "serial::ShapeRecord")]
+pub struct Ellipse {
+    radius_x: f64,
+    radius_y: f64,
+    position: Point,
+    zero_policy: ZeroPolicy,
+}
+
+/// A regular polygon with `sides` sides of length `side_length`,
+/// positioned by its centre and resting on a horizontal bottom side.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "serial::ShapeRecord", try_from = "serial::ShapeRecord")]
+pub struct RegularPolygon {
+    sides: usize,
+    side_length: f64,
+    position: Point,
+    zero_policy: ZeroPolicy,
+}
+
+/// A point in the plane.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
 }
 
-#[derive(Debug, PartialEq)]
+/// An axis-aligned box spanning from `min` to `max`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: Point,
+    pub max: Point,
+}
+
+/// A simple (non-self-intersecting) polygon given by its vertices in
+/// order, either clockwise or counter-clockwise. It is positioned by its
+/// first vertex.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "serial::ShapeRecord", try_from = "serial::ShapeRecord")]
+pub struct Polygon {
+    vertices: Vec<Point>,
+}
+
+/// Any one of the shapes, for operations such as `Transform` whose result
+/// may be a different kind of shape than they started with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "serial::ShapeRecord", try_from = "serial::ShapeRecord")]
+pub enum AnyShape {
+    Rectangle(Rectangle),
+    Circle(Circle),
+    Square(Square),
+    Triangle(Triangle),
+    Ellipse(Ellipse),
+    RegularPolygon(RegularPolygon),
+    Polygon(Polygon),
+}
+
+/// Whether shapes accept dimensions of zero. Shapes allow them unless
+/// created with `with_zero_policy(ZeroPolicy::Reject)`, which also makes
+/// their setters reject zero. `Polygon` has no policy: a polygon with zero
+/// area always fails the self-intersection check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZeroPolicy {
+    #[default]
+    Allow,
+    Reject,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     InvalidWidth,
     InvalidHeight,
     InvalidRadius,
+    /// A negative depth of a solid.
+    InvalidDepth,
+    /// A negative side length.
+    InvalidSide,
+    /// Side lengths that violate the triangle inequality.
+    InvalidTriangle,
+    /// A regular polygon with fewer than three sides, or more than
+    /// `RegularPolygon::MAX_SIDES`.
+    InvalidSideCount,
+    /// A polygon with fewer than three vertices.
+    TooFewVertices,
+    /// A polygon whose edges cross or touch each other.
+    SelfIntersecting,
+    /// A NaN or infinite value for the named dimension or coordinate.
+    NonFinite {
+        field: &'static str,
+    },
+    /// A zero value for the named dimension under `ZeroPolicy::Reject`.
+    /// Degenerate triangles report the field `"area"`.
+    ZeroSize {
+        field: &'static str,
+    },
+    /// A negative scale factor.
+    InvalidScale,
+    /// A transform whose result can't be represented, such as rotating an
+    /// ellipse by an angle that is not a multiple of 90°, or turning a
+    /// shape without area, or one flattened onto a line, into a `Polygon`.
+    UnsupportedTransform,
+    /// A pair of shapes whose intersection area can't be computed exactly.
+    UnsupportedIntersection,
+    /// Quantities in different units combined under `UnitPolicy::Reject`.
+    UnitMismatch {
+        left: units::Unit,
+        right: units::Unit,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidWidth => write!(f, "width must not be negative"),
+            Error::InvalidHeight => write!(f, "height must not be negative"),
+            Error::InvalidRadius => write!(f, "radius must not be negative"),
+            Error::InvalidDepth => write!(f, "depth must not be negative"),
+            Error::InvalidSide => write!(f, "side length must not be negative"),
+            Error::InvalidTriangle => {
+                write!(f, "side lengths violate the triangle inequality")
+            }
+            Error::InvalidSideCount => write!(
+                f,
+                "a regular polygon needs between 3 and {} sides",
+                RegularPolygon::MAX_SIDES
+            ),
+            Error::TooFewVertices => write!(f, "a polygon needs at least three vertices"),
+            Error::SelfIntersecting => write!(f, "polygon edges intersect"),
+            Error::NonFinite { field } => write!(f, "{} must be finite", field),
+            Error::ZeroSize { field } => write!(f, "{} must not be zero", field),
+            Error::InvalidScale => write!(f, "scale factor must not be negative"),
+            Error::UnsupportedTransform => {
+                write!(f, "the transformed shape can't be represented")
+            }
+            Error::UnsupportedIntersection => {
+                write!(f, "no exact intersection area for these shapes")
+            }
+            Error::UnitMismatch { left, right } => {
+                write!(f, "can't combine {} with {}", left, right)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Checks a single dimension: NaN and infinities are `NonFinite`,
+/// negative values are the shape's own `negative` error and zero is only
+/// rejected under `ZeroPolicy::Reject`.
+fn check_dimension(
+    value: f64,
+    field: &'static str,
+    negative: Error,
+    zero_policy: ZeroPolicy,
+) -> Result<(), Error> {
+    if !value.is_finite() {
+        return Err(Error::NonFinite { field });
+    }
+    if value < 0.0 {
+        return Err(negative);
+    }
+    if value == 0.0 && zero_policy == ZeroPolicy::Reject {
+        return Err(Error::ZeroSize { field });
+    }
+    Ok(())
 }
 
+fn check_sides(sides: usize) -> Result<(), Error> {
+    if !(3..=RegularPolygon::MAX_SIDES).contains(&sides) {
+        return Err(Error::InvalidSideCount);
+    }
+    Ok(())
+}
+
+/// Rejects a position with a NaN or infinite coordinate.
+fn check_position(position: Point) -> Result<(), Error> {
+    if !position.is_finite() {
+        return Err(Error::NonFinite { field: "position" });
+    }
+    Ok(())
+}
+
+/// Implements `with_position` and `set_position` for shapes that keep
+/// their position in a `position` field.
+macro_rules! positioned {
+    ($($shape:ty),*) => {
+        $(
+            impl $shape {
+                /// Moves the shape to `position`.
+                pub fn with_position(mut self, position: Point) -> Result<Self, Error> {
+                    self.set_position(position)?;
+                    Ok(self)
+                }
+                pub fn set_position(&mut self, position: Point) -> Result<(), Error> {
+                    check_position(position)?;
+                    self.position = position;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+positioned!(Rectangle, Circle, Square, Triangle, Ellipse, RegularPolygon);
+
 // TODO: Implement constructor with setters and getters.
 //
 // Width and height are considered invalid if they are negative.
 // All methods should return the corresponding error when invalid values are provided.
+//
+// NaN and infinite values are rejected as well, and zero under
+// `ZeroPolicy::Reject`.
 impl Rectangle {
     pub fn new(width: f64, height: f64) -> Result<Self, Error> {
-        if height < 0.0 {
-            return Err(Error::InvalidHeight);
-        } else if width < 0.0 {
-            return Err(Error::InvalidWidth);
-        }
-        Ok(Self { width, height })
+        let rectangle = Self {
+            width,
+            height,
+            position: Point::default(),
+            zero_policy: ZeroPolicy::Allow,
+        };
+        rectangle.validate()?;
+        Ok(rectangle)
+    }
+    /// Applies `zero_policy` to the current and any later dimensions.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
     }
     pub fn set_width(&mut self, width: f64) -> Result<(), Error> {
-        if width < 0.0 {
-            return Err(Error::InvalidWidth);
-        }
+        check_dimension(width, "width", Error::InvalidWidth, self.zero_policy)?;
         self.width = width;
         Ok(())
     }
     pub fn set_height(&mut self, height: f64) -> Result<(), Error> {
-        if height < 0.0 {
-            return Err(Error::InvalidHeight);
-        }
+        check_dimension(height, "height", Error::InvalidHeight, self.zero_policy)?;
         self.height = height;
         Ok(())
     }
@@ -68,29 +348,58 @@ impl Rectangle {
     pub fn get_height(&self) -> f64 {
         self.height
     }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_dimension(
+            self.height,
+            "height",
+            Error::InvalidHeight,
+            self.zero_policy,
+        )?;
+        check_dimension(self.width, "width", Error::InvalidWidth, self.zero_policy)
+    }
 }
 
 // TODO: Implement constructor with setter and getter.
 //
 // The radius is considered invalid if it is negative.
 // All methods should return the corresponding error when invalid values are provided.
+//
+// NaN and infinite values are rejected as well, and zero under
+// `ZeroPolicy::Reject`.
 impl Circle {
     pub fn new(radius: f64) -> Result<Self, Error> {
-        if radius < 0.0 {
-            return Err(Error::InvalidRadius);
-        }
-        Ok(Self { radius })
+        let circle = Self {
+            radius,
+            position: Point::default(),
+            zero_policy: ZeroPolicy::Allow,
+        };
+        circle.validate()?;
+        Ok(circle)
+    }
+    /// Applies `zero_policy` to the current and any later radius.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
     }
     pub fn set_radius(&mut self, radius: f64) -> Result<(), Error> {
-        if radius < 0.0 {
-            return Err(Error::InvalidRadius);
-        }
+        check_dimension(radius, "radius", Error::InvalidRadius, self.zero_policy)?;
         self.radius = radius;
         Ok(())
     }
     pub fn get_radius(&self) -> f64 {
         self.radius
     }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_dimension(
+            self.radius,
+            "radius",
+            Error::InvalidRadius,
+            self.zero_policy,
+        )
+    }
 }
 
 // TODO: Implement the Shape trait for both Rectangle and Circle structs.
@@ -102,6 +411,31 @@ impl Shape for Rectangle {
     fn perimeter(&self) -> f64 {
         2.0 * (self.height + self.width)
     }
+
+    fn position(&self) -> Point {
+        self.position
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let max = Point::new(self.position.x + self.width, self.position.y + self.height);
+        BoundingBox::new(self.position, max)
+    }
+
+    fn centroid(&self) -> Point {
+        self.bounding_box().center()
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        self.bounding_box().contains(point)
+    }
+
+    fn distance_to(&self, point: Point) -> f64 {
+        self.bounding_box().distance_to(point)
+    }
+
+    fn geometry(&self) -> Geometry {
+        Geometry::Box(self.bounding_box())
+    }
 }
 impl Shape for Circle {
     // area of circle -> PI*r*r
@@ -112,5 +446,769 @@ impl Shape for Circle {
     fn perimeter(&self) -> f64 {
         2.0 * PI * self.radius
     }
+
+    fn position(&self) -> Point {
+        self.position
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let (center, r) = (self.position, self.radius);
+        BoundingBox::new(
+            Point::new(center.x - r, center.y - r),
+            Point::new(center.x + r, center.y + r),
+        )
+    }
+
+    fn centroid(&self) -> Point {
+        self.position
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        self.position.distance(point) <= self.radius
+    }
+
+    fn distance_to(&self, point: Point) -> f64 {
+        (self.position.distance(point) - self.radius).max(0.0)
+    }
+    fn geometry(&self) -> Geometry {
+        Geometry::Circle {
+            center: self.position,
+            radius: self.radius,
+        }
+    }
 }
 // Hint: Use std::f64::consts::PI to calculate the area and circumference of the circle.
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    fn distance(self, other: Point) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+
+    fn is_finite(self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+}
+
+impl BoundingBox {
+    pub fn new(min: Point, max: Point) -> Self {
+        Self { min, max }
+    }
+    pub fn width(&self) -> f64 {
+        self.max.x - self.min.x
+    }
+    pub fn height(&self) -> f64 {
+        self.max.y - self.min.y
+    }
+    pub fn center(&self) -> Point {
+        Point::new(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+        )
+    }
+    /// Whether `point` lies inside the box or on its edges.
+    pub fn contains(&self, point: Point) -> bool {
+        within_box(self.min, self.max, point)
+    }
+
+    fn distance_to(&self, point: Point) -> f64 {
+        let dx = (self.min.x - point.x).max(point.x - self.max.x).max(0.0);
+        let dy = (self.min.y - point.y).max(point.y - self.max.y).max(0.0);
+        dx.hypot(dy)
+    }
+}
+
+impl Square {
+    pub fn new(side: f64) -> Result<Self, Error> {
+        let square = Self {
+            side,
+            position: Point::default(),
+            zero_policy: ZeroPolicy::Allow,
+        };
+        square.validate()?;
+        Ok(square)
+    }
+    /// Applies `zero_policy` to the current and any later side.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
+    }
+    pub fn set_side(&mut self, side: f64) -> Result<(), Error> {
+        check_dimension(side, "side", Error::InvalidSide, self.zero_policy)?;
+        self.side = side;
+        Ok(())
+    }
+    pub fn get_side(&self) -> f64 {
+        self.side
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_dimension(self.side, "side", Error::InvalidSide, self.zero_policy)
+    }
+}
+
+impl Shape for Square {
+    fn area(&self) -> f64 {
+        self.side * self.side
+    }
+
+    fn perimeter(&self) -> f64 {
+        4.0 * self.side
+    }
+
+    fn position(&self) -> Point {
+        self.position
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let max = Point::new(self.position.x + self.side, self.position.y + self.side);
+        BoundingBox::new(self.position, max)
+    }
+
+    fn centroid(&self) -> Point {
+        self.bounding_box().center()
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        self.bounding_box().contains(point)
+    }
+
+    fn distance_to(&self, point: Point) -> f64 {
+        self.bounding_box().distance_to(point)
+    }
+
+    fn geometry(&self) -> Geometry {
+        Geometry::Box(self.bounding_box())
+    }
+}
+
+impl Triangle {
+    /// Degenerate triangles, where one side equals the sum of the other
+    /// two, are allowed and have zero area unless zero is rejected.
+    pub fn new(a: f64, b: f64, c: f64) -> Result<Self, Error> {
+        let triangle = Self {
+            a,
+            b,
+            c,
+            position: Point::default(),
+            zero_policy: ZeroPolicy::Allow,
+        };
+        triangle.validate()?;
+        Ok(triangle)
+    }
+    /// Applies `zero_policy` to the current and any later sides.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
+    }
+    pub fn set_sides(&mut self, a: f64, b: f64, c: f64) -> Result<(), Error> {
+        let triangle = Self {
+            a,
+            b,
+            c,
+            position: self.position,
+            zero_policy: self.zero_policy,
+        };
+        triangle.validate()?;
+        *self = triangle;
+        Ok(())
+    }
+    pub fn get_sides(&self) -> (f64, f64, f64) {
+        (self.a, self.b, self.c)
+    }
+    /// The vertices in counter-clockwise order, starting at the position.
+    pub fn vertices(&self) -> [Point; 3] {
+        let (a, b, c) = self.get_sides();
+        // The third vertex, relative to the position, from the law of
+        // cosines. With c == 0 the triangle inequality forces a == b.
+        let x = if c > 0.0 {
+            (b * b + c * c - a * a) / (2.0 * c)
+        } else {
+            0.0
+        };
+        let y = (b * b - x * x).max(0.0).sqrt();
+        let origin = self.position;
+        [
+            origin,
+            Point::new(origin.x + c, origin.y),
+            Point::new(origin.x + x, origin.y + y),
+        ]
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let (a, b, c) = self.get_sides();
+        for side in [a, b, c] {
+            check_dimension(side, "side", Error::InvalidSide, self.zero_policy)?;
+        }
+        if a > b + c || b > a + c || c > a + b {
+            return Err(Error::InvalidTriangle);
+        }
+        if self.zero_policy == ZeroPolicy::Reject && self.area() == 0.0 {
+            return Err(Error::ZeroSize { field: "area" });
+        }
+        Ok(())
+    }
+}
+
+impl Shape for Triangle {
+    // Heron's formula, arranged to stay accurate for needle-like
+    // triangles (Kahan, "Miscalculating Area and Angles of a Needle-like
+    // Triangle"). Needs the sides sorted so that a >= b >= c.
+    fn area(&self) -> f64 {
+        let mut sides = [self.a, self.b, self.c];
+        sides.sort_by(|x, y| y.total_cmp(x));
+        let [a, b, c] = sides;
+        let product = (a + (b + c)) * (c - (a - b)) * (c + (a - b)) * (a + (b - c));
+        // Rounding can push a degenerate triangle slightly below zero.
+        0.25 * product.max(0.0).sqrt()
+    }
+
+    fn perimeter(&self) -> f64 {
+        self.a + self.b + self.c
+    }
+
+    fn position(&self) -> Point {
+        self.position
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        bounding_box_of(&self.vertices())
+    }
+
+    fn centroid(&self) -> Point {
+        let [p, q, r] = self.vertices();
+        Point::new((p.x + q.x + r.x) / 3.0, (p.y + q.y + r.y) / 3.0)
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        polygon_contains(&self.vertices(), point)
+    }
+
+    fn distance_to(&self, point: Point) -> f64 {
+        polygon_distance(&self.vertices(), point)
+    }
+
+    fn geometry(&self) -> Geometry {
+        Geometry::Polygon(self.vertices().to_vec())
+    }
+}
+
+impl Ellipse {
+    pub fn new(radius_x: f64, radius_y: f64) -> Result<Self, Error> {
+        let ellipse = Self {
+            radius_x,
+            radius_y,
+            position: Point::default(),
+            zero_policy: ZeroPolicy::Allow,
+        };
+        ellipse.validate()?;
+        Ok(ellipse)
+    }
+    /// Applies `zero_policy` to the current and any later radii.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
+    }
+    pub fn set_radius_x(&mut self, radius_x: f64) -> Result<(), Error> {
+        check_dimension(radius_x, "radius_x", Error::InvalidRadius, self.zero_policy)?;
+        self.radius_x = radius_x;
+        Ok(())
+    }
+    pub fn set_radius_y(&mut self, radius_y: f64) -> Result<(), Error> {
+        check_dimension(radius_y, "radius_y", Error::InvalidRadius, self.zero_policy)?;
+        self.radius_y = radius_y;
+        Ok(())
+    }
+    pub fn get_radius_x(&self) -> f64 {
+        self.radius_x
+    }
+    pub fn get_radius_y(&self) -> f64 {
+        self.radius_y
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_dimension(
+            self.radius_x,
+            "radius_x",
+            Error::InvalidRadius,
+            self.zero_policy,
+        )?;
+        check_dimension(
+            self.radius_y,
+            "radius_y",
+            Error::InvalidRadius,
+            self.zero_policy,
+        )
+    }
+}
+
+impl Shape for Ellipse {
+    fn area(&self) -> f64 {
+        PI * self.radius_x * self.radius_y
+    }
+
+    // Ramanujan's second approximation; exact for circles and within
+    // about 0.04% even for a flat ellipse.
+    fn perimeter(&self) -> f64 {
+        let (a, b) = (self.radius_x, self.radius_y);
+        if a + b == 0.0 {
+            return 0.0;
+        }
+        let h = ((a - b) / (a + b)).powi(2);
+        PI * (a + b) * (1.0 + 3.0 * h / (10.0 + (4.0 - 3.0 * h).sqrt()))
+    }
+
+    fn position(&self) -> Point {
+        self.position
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let (center, rx, ry) = (self.position, self.radius_x, self.radius_y);
+        BoundingBox::new(
+            Point::new(center.x - rx, center.y - ry),
+            Point::new(center.x + rx, center.y + ry),
+        )
+    }
+
+    fn centroid(&self) -> Point {
+        self.position
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        let (a, b) = (self.radius_x, self.radius_y);
+        let (dx, dy) = (point.x - self.position.x, point.y - self.position.y);
+        if a == 0.0 || b == 0.0 {
+            // A flat ellipse is just the segment between its extremes.
+            return self.distance_to(point) == 0.0;
+        }
+        (dx / a).powi(2) + (dy / b).powi(2) <= 1.0
+    }
+
+    fn distance_to(&self, point: Point) -> f64 {
+        ellipse_distance(self.position, self.radius_x, self.radius_y, point)
+    }
+    fn geometry(&self) -> Geometry {
+        Geometry::Ellipse {
+            center: self.position,
+            radius_x: self.radius_x,
+            radius_y: self.radius_y,
+        }
+    }
+}
+
+impl RegularPolygon {
+    /// The most sides a regular polygon may have. Its vertices are
+    /// computed whenever it is placed, drawn or collided, and colliding two
+    /// polygons takes time proportional to the product of their vertex
+    /// counts, so the count must stay small.
+    pub const MAX_SIDES: usize = 1024;
+
+    pub fn new(sides: usize, side_length: f64) -> Result<Self, Error> {
+        let polygon = Self {
+            sides,
+            side_length,
+            position: Point::default(),
+            zero_policy: ZeroPolicy::Allow,
+        };
+        polygon.validate()?;
+        Ok(polygon)
+    }
+    /// Applies `zero_policy` to the current and any later side length.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
+    }
+    pub fn set_sides(&mut self, sides: usize) -> Result<(), Error> {
+        check_sides(sides)?;
+        self.sides = sides;
+        Ok(())
+    }
+    pub fn set_side_length(&mut self, side_length: f64) -> Result<(), Error> {
+        check_dimension(
+            side_length,
+            "side_length",
+            Error::InvalidSide,
+            self.zero_policy,
+        )?;
+        self.side_length = side_length;
+        Ok(())
+    }
+    pub fn get_sides(&self) -> usize {
+        self.sides
+    }
+    pub fn get_side_length(&self) -> f64 {
+        self.side_length
+    }
+    /// The vertices in counter-clockwise order, starting at the right end
+    /// of the bottom side.
+    pub fn vertices(&self) -> Vec<Point> {
+        let n = self.sides as f64;
+        let circumradius = self.side_length / (2.0 * (PI / n).sin());
+        (0..self.sides)
+            .map(|k| {
+                let angle = -PI / 2.0 + PI / n + 2.0 * PI * k as f64 / n;
+                Point::new(
+                    self.position.x + circumradius * angle.cos(),
+                    self.position.y + circumradius * angle.sin(),
+                )
+            })
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_sides(self.sides)?;
+        check_dimension(
+            self.side_length,
+            "side_length",
+            Error::InvalidSide,
+            self.zero_policy,
+        )
+    }
+}
+
+impl Shape for RegularPolygon {
+    fn area(&self) -> f64 {
+        let n = self.sides as f64;
+        n * self.side_length * self.side_length / (4.0 * (PI / n).tan())
+    }
+
+    fn perimeter(&self) -> f64 {
+        self.sides as f64 * self.side_length
+    }
+
+    fn position(&self) -> Point {
+        self.position
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        bounding_box_of(&self.vertices())
+    }
+
+    fn centroid(&self) -> Point {
+        self.position
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        polygon_contains(&self.vertices(), point)
+    }
+
+    fn distance_to(&self, point: Point) -> f64 {
+        polygon_distance(&self.vertices(), point)
+    }
+
+    fn geometry(&self) -> Geometry {
+        Geometry::Polygon(self.vertices())
+    }
+}
+
+impl Polygon {
+    pub fn new(vertices: Vec<Point>) -> Result<Self, Error> {
+        if !vertices.iter().all(|p| p.is_finite()) {
+            return Err(Error::NonFinite { field: "vertex" });
+        }
+        if vertices.len() < 3 {
+            return Err(Error::TooFewVertices);
+        }
+        if is_self_intersecting(&vertices) {
+            return Err(Error::SelfIntersecting);
+        }
+        Ok(Self { vertices })
+    }
+    pub fn get_vertices(&self) -> &[Point] {
+        &self.vertices
+    }
+    /// Moves every vertex by the same amount, so that the first vertex
+    /// ends up at `position`.
+    pub fn with_position(mut self, position: Point) -> Result<Self, Error> {
+        self.set_position(position)?;
+        Ok(self)
+    }
+    pub fn set_position(&mut self, position: Point) -> Result<(), Error> {
+        check_position(position)?;
+        let (dx, dy) = (
+            position.x - self.vertices[0].x,
+            position.y - self.vertices[0].y,
+        );
+        for vertex in &mut self.vertices {
+            *vertex = Point::new(vertex.x + dx, vertex.y + dy);
+        }
+        Ok(())
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        edges(&self.vertices)
+    }
+}
+
+impl Shape for Polygon {
+    // Shoelace formula.
+    fn area(&self) -> f64 {
+        let twice_signed: f64 = self.edges().map(|(p, q)| p.x * q.y - q.x * p.y).sum();
+        twice_signed.abs() / 2.0
+    }
+
+    fn perimeter(&self) -> f64 {
+        self.edges().map(|(p, q)| p.distance(q)).sum()
+    }
+
+    fn position(&self) -> Point {
+        self.vertices[0]
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        bounding_box_of(&self.vertices)
+    }
+
+    // The centroids of the triangles fanning out from the origin,
+    // weighted by their signed areas.
+    fn centroid(&self) -> Point {
+        let (mut cx, mut cy, mut twice_area) = (0.0, 0.0, 0.0);
+        for (p, q) in self.edges() {
+            let cross = p.x * q.y - q.x * p.y;
+            cx += (p.x + q.x) * cross;
+            cy += (p.y + q.y) * cross;
+            twice_area += cross;
+        }
+        Point::new(cx / (3.0 * twice_area), cy / (3.0 * twice_area))
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        polygon_contains(&self.vertices, point)
+    }
+
+    fn distance_to(&self, point: Point) -> f64 {
+        polygon_distance(&self.vertices, point)
+    }
+    fn geometry(&self) -> Geometry {
+        Geometry::Polygon(self.vertices.clone())
+    }
+}
+
+/// Consecutive vertex pairs, including the closing edge from the last
+/// vertex back to the first.
+fn edges(vertices: &[Point]) -> impl Iterator<Item = (Point, Point)> + '_ {
+    let next = vertices.iter().cycle().skip(1);
+    vertices.iter().copied().zip(next.copied())
+}
+
+/// Evaluates `$body` with `$shape` bound to the shape inside `$any`.
+macro_rules! dispatch {
+    ($any:expr, $shape:ident => $body:expr) => {
+        match $any {
+            AnyShape::Rectangle($shape) => $body,
+            AnyShape::Circle($shape) => $body,
+            AnyShape::Square($shape) => $body,
+            AnyShape::Triangle($shape) => $body,
+            AnyShape::Ellipse($shape) => $body,
+            AnyShape::RegularPolygon($shape) => $body,
+            AnyShape::Polygon($shape) => $body,
+        }
+    };
+}
+use dispatch;
+
+impl Shape for AnyShape {
+    fn area(&self) -> f64 {
+        dispatch!(self, shape => shape.area())
+    }
+
+    fn perimeter(&self) -> f64 {
+        dispatch!(self, shape => shape.perimeter())
+    }
+
+    fn position(&self) -> Point {
+        dispatch!(self, shape => shape.position())
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        dispatch!(self, shape => shape.bounding_box())
+    }
+
+    fn centroid(&self) -> Point {
+        dispatch!(self, shape => shape.centroid())
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        dispatch!(self, shape => shape.contains(point))
+    }
+
+    fn distance_to(&self, point: Point) -> f64 {
+        dispatch!(self, shape => shape.distance_to(point))
+    }
+    fn geometry(&self) -> Geometry {
+        dispatch!(self, shape => shape.geometry())
+    }
+}
+
+macro_rules! any_shape_from {
+    ($($shape:ident),*) => {
+        $(
+            impl From<$shape> for AnyShape {
+                fn from(shape: $shape) -> Self {
+                    AnyShape::$shape(shape)
+                }
+            }
+        )*
+    };
+}
+
+any_shape_from!(
+    Rectangle,
+    Circle,
+    Square,
+    Triangle,
+    Ellipse,
+    RegularPolygon,
+    Polygon
+);
+
+fn bounding_box_of(points: &[Point]) -> BoundingBox {
+    let (first, rest) = points.split_first().expect("shapes have vertices");
+    rest.iter()
+        .fold(BoundingBox::new(*first, *first), |bounds, p| {
+            BoundingBox::new(
+                Point::new(bounds.min.x.min(p.x), bounds.min.y.min(p.y)),
+                Point::new(bounds.max.x.max(p.x), bounds.max.y.max(p.y)),
+            )
+        })
+}
+
+/// Whether `point` is on an edge of the polygon or inside it, by counting
+/// the edges a ray from `point` towards +x crosses.
+fn polygon_contains(vertices: &[Point], point: Point) -> bool {
+    let mut inside = false;
+    for (p, q) in edges(vertices) {
+        if orientation(p, q, point) == 0.0 && within_box(p, q, point) {
+            return true;
+        }
+        if (p.y > point.y) != (q.y > point.y) {
+            let x = p.x + (point.y - p.y) / (q.y - p.y) * (q.x - p.x);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn polygon_distance(vertices: &[Point], point: Point) -> f64 {
+    if polygon_contains(vertices, point) {
+        return 0.0;
+    }
+    edges(vertices)
+        .map(|(p, q)| segment_distance(point, p, q))
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// The distance from `point` to the nearest point of the segment `ab`.
+fn segment_distance(point: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length_squared = dx * dx + dy * dy;
+    if length_squared == 0.0 {
+        return point.distance(a);
+    }
+    let t = (((point.x - a.x) * dx + (point.y - a.y) * dy) / length_squared).clamp(0.0, 1.0);
+    point.distance(Point::new(a.x + t * dx, a.y + t * dy))
+}
+
+/// The distance from `point` to the nearest point of an axis-aligned
+/// ellipse, which is zero for points inside it.
+//
+// By symmetry it is enough to find the nearest boundary point in the
+// first quadrant. Each step approximates the ellipse near the current
+// guess by its circle of curvature, whose centre lies on the evolute.
+fn ellipse_distance(center: Point, radius_x: f64, radius_y: f64, point: Point) -> f64 {
+    let (a, b) = (radius_x, radius_y);
+    let (px, py) = ((point.x - center.x).abs(), (point.y - center.y).abs());
+    if a == 0.0 || b == 0.0 {
+        let end = Point::new(a, b);
+        return segment_distance(Point::new(px, py), Point::new(-a, -b), end);
+    }
+    if (px / a).powi(2) + (py / b).powi(2) <= 1.0 {
+        return 0.0;
+    }
+    let (mut tx, mut ty) = (FRAC_1_SQRT_2, FRAC_1_SQRT_2);
+    for _ in 0..8 {
+        let (x, y) = (a * tx, b * ty);
+        let ex = (a * a - b * b) * tx.powi(3) / a;
+        let ey = (b * b - a * a) * ty.powi(3) / b;
+        let r = (x - ex).hypot(y - ey);
+        let (qx, qy) = (px - ex, py - ey);
+        let q = qx.hypot(qy);
+        tx = ((qx * r / q + ex) / a).clamp(0.0, 1.0);
+        ty = ((qy * r / q + ey) / b).clamp(0.0, 1.0);
+        let t = tx.hypot(ty);
+        tx /= t;
+        ty /= t;
+    }
+    (px - a * tx).hypot(py - b * ty)
+}
+
+/// Whether any two edges of the polygon cross or touch, other than
+/// neighbouring edges meeting at their shared vertex.
+fn is_self_intersecting(vertices: &[Point]) -> bool {
+    let edges: Vec<(Point, Point)> = edges(vertices).collect();
+    let n = edges.len();
+    for i in 0..n {
+        for j in i + 1..n {
+            let (p1, p2) = edges[i];
+            let (q1, q2) = edges[j];
+            // Neighbouring edges always meet at their shared vertex; they
+            // only overlap if they fold back along the same line.
+            let shared = if j == i + 1 {
+                Some((p1, p2, q2))
+            } else if i == 0 && j == n - 1 {
+                Some((p2, p1, q1))
+            } else {
+                None
+            };
+            let overlaps = match shared {
+                Some((a, vertex, b)) => {
+                    let dot =
+                        (a.x - vertex.x) * (b.x - vertex.x) + (a.y - vertex.y) * (b.y - vertex.y);
+                    orientation(a, vertex, b) == 0.0 && dot > 0.0
+                }
+                None => segments_intersect(p1, p2, q1, q2),
+            };
+            if overlaps {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Twice the signed area of the triangle pqr: positive if counter-clockwise,
+/// negative if clockwise and zero if the points are collinear.
+fn orientation(p: Point, q: Point, r: Point) -> f64 {
+    (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x)
+}
+
+fn segments_intersect(p1: Point, p2: Point, q1: Point, q2: Point) -> bool {
+    let d1 = orientation(q1, q2, p1);
+    let d2 = orientation(q1, q2, p2);
+    let d3 = orientation(p1, p2, q1);
+    let d4 = orientation(p1, p2, q2);
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return true;
+    }
+    let touches = |d: f64, a: Point, b: Point, c: Point| d == 0.0 && within_box(a, b, c);
+    touches(d1, q1, q2, p1)
+        || touches(d2, q1, q2, p2)
+        || touches(d3, p1, p2, q1)
+        || touches(d4, p1, p2, q2)
+}
+
+/// Whether `c` lies in the bounding box of the segment `ab`.
+fn within_box(a: Point, b: Point, c: Point) -> bool {
+    c.x >= a.x.min(b.x) && c.x <= a.x.max(b.x) && c.y >= a.y.min(b.y) && c.y <= a.y.max(b.y)
+}
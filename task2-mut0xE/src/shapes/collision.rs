@@ -0,0 +1,392 @@
+use std::f64::consts::PI;
+
+use super::{
+    edges, ellipse_distance, orientation, polygon_contains, polygon_distance, segments_intersect,
+    BoundingBox, Error, Point,
+};
+
+/// The outline of a placed shape, in absolute coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    /// An axis-aligned rectangle or square.
+    Box(BoundingBox),
+    Circle {
+        center: Point,
+        radius: f64,
+    },
+    /// An axis-aligned ellipse.
+    Ellipse {
+        center: Point,
+        radius_x: f64,
+        radius_y: f64,
+    },
+    /// A simple polygon, in either orientation.
+    Polygon(Vec<Point>),
+}
+
+impl Geometry {
+    /// Replaces ellipses that are really circles or line segments with
+    /// those, so that the cases below only see proper ellipses.
+    fn normalized(&self) -> Geometry {
+        match *self {
+            Geometry::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+            } if radius_x == radius_y => Geometry::Circle {
+                center,
+                radius: radius_x,
+            },
+            Geometry::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+            } if radius_x == 0.0 || radius_y == 0.0 => Geometry::Polygon(vec![
+                Point::new(center.x - radius_x, center.y - radius_y),
+                Point::new(center.x + radius_x, center.y + radius_y),
+            ]),
+            _ => self.clone(),
+        }
+    }
+
+    /// The distance from `point` to the nearest point of the outline, or
+    /// zero if it lies inside.
+    fn distance_to(&self, point: Point) -> f64 {
+        match self {
+            Geometry::Box(bounds) => bounds.distance_to(point),
+            Geometry::Circle { center, radius } => (center.distance(point) - radius).max(0.0),
+            Geometry::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+            } => ellipse_distance(*center, *radius_x, *radius_y, point),
+            Geometry::Polygon(vertices) => polygon_distance(vertices, point),
+        }
+    }
+
+    /// Moves `origin` to the origin and then scales by `sx` and `sy`,
+    /// which must both be positive.
+    fn scaled(&self, origin: Point, sx: f64, sy: f64) -> Geometry {
+        let map = |p: Point| Point::new((p.x - origin.x) * sx, (p.y - origin.y) * sy);
+        match self {
+            Geometry::Box(bounds) => {
+                Geometry::Box(BoundingBox::new(map(bounds.min), map(bounds.max)))
+            }
+            Geometry::Circle { center, radius } => Geometry::Ellipse {
+                center: map(*center),
+                radius_x: radius * sx,
+                radius_y: radius * sy,
+            },
+            Geometry::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+            } => Geometry::Ellipse {
+                center: map(*center),
+                radius_x: radius_x * sx,
+                radius_y: radius_y * sy,
+            },
+            Geometry::Polygon(vertices) => {
+                Geometry::Polygon(vertices.iter().map(|&p| map(p)).collect())
+            }
+        }
+    }
+
+    /// The vertices of a box or polygon.
+    fn vertices(&self) -> Option<Vec<Point>> {
+        match self {
+            Geometry::Box(bounds) => Some(vec![
+                bounds.min,
+                Point::new(bounds.max.x, bounds.min.y),
+                bounds.max,
+                Point::new(bounds.min.x, bounds.max.y),
+            ]),
+            Geometry::Polygon(vertices) => Some(vertices.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the two outlines overlap or touch.
+pub fn intersects(a: &Geometry, b: &Geometry) -> bool {
+    match (a.normalized(), b.normalized()) {
+        (Geometry::Box(p), Geometry::Box(q)) => {
+            p.min.x <= q.max.x && q.min.x <= p.max.x && p.min.y <= q.max.y && q.min.y <= p.max.y
+        }
+        (Geometry::Circle { center, radius }, other)
+        | (other, Geometry::Circle { center, radius }) => other.distance_to(center) <= radius,
+        // Scaling the plane turns the ellipse into the unit circle, which
+        // the case above handles.
+        (
+            Geometry::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+            },
+            other,
+        )
+        | (
+            other,
+            Geometry::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+            },
+        ) => {
+            other
+                .scaled(center, 1.0 / radius_x, 1.0 / radius_y)
+                .distance_to(Point::default())
+                <= 1.0
+        }
+        (a, b) => {
+            let (p, q) = (a.vertices().unwrap(), b.vertices().unwrap());
+            if is_convex(&p) && is_convex(&q) {
+                !has_separating_axis(&p, &q) && !has_separating_axis(&q, &p)
+            } else {
+                polygons_intersect(&p, &q)
+            }
+        }
+    }
+}
+
+/// The area of the region inside both outlines. Outlines that don't
+/// intersect always give zero, even where there is no exact formula.
+pub fn intersection_area(a: &Geometry, b: &Geometry) -> Result<f64, Error> {
+    if !intersects(a, b) {
+        return Ok(0.0);
+    }
+    let area = match (a.normalized(), b.normalized()) {
+        (Geometry::Box(p), Geometry::Box(q)) => {
+            let width = p.max.x.min(q.max.x) - p.min.x.max(q.min.x);
+            let height = p.max.y.min(q.max.y) - p.min.y.max(q.min.y);
+            width.max(0.0) * height.max(0.0)
+        }
+        (
+            Geometry::Circle {
+                center: c1,
+                radius: r1,
+            },
+            Geometry::Circle {
+                center: c2,
+                radius: r2,
+            },
+        ) => lens_area(r1, r2, c1.distance(c2)),
+        (Geometry::Circle { center, radius }, other)
+        | (other, Geometry::Circle { center, radius })
+            if other.vertices().is_some() =>
+        {
+            circle_polygon_area(center, radius, &other.vertices().unwrap())
+        }
+        (
+            Geometry::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+            },
+            other,
+        )
+        | (
+            other,
+            Geometry::Ellipse {
+                center,
+                radius_x,
+                radius_y,
+            },
+        ) => {
+            // In the plane scaled so that this ellipse is the unit circle,
+            // areas shrink by radius_x * radius_y.
+            let scaled = other.scaled(center, 1.0 / radius_x, 1.0 / radius_y);
+            let unit = Point::default();
+            let area = match (&other, &scaled) {
+                (_, Geometry::Box(_) | Geometry::Polygon(_)) => {
+                    circle_polygon_area(unit, 1.0, &scaled.vertices().unwrap())
+                }
+                // Another ellipse with the same aspect ratio becomes a circle.
+                (
+                    Geometry::Ellipse {
+                        radius_x: rx,
+                        radius_y: ry,
+                        ..
+                    },
+                    Geometry::Ellipse {
+                        center: scaled_center,
+                        ..
+                    },
+                ) if rx * radius_y == ry * radius_x => {
+                    lens_area(1.0, rx / radius_x, scaled_center.distance(unit))
+                }
+                _ => return Err(Error::UnsupportedIntersection),
+            };
+            area * radius_x * radius_y
+        }
+        (a, b) => polygon_overlap_area(&a.vertices().unwrap(), &b.vertices().unwrap()),
+    };
+    Ok(area)
+}
+
+/// The area of the lens where circles of radius `r1` and `r2`, with
+/// centres `d` apart, overlap.
+fn lens_area(r1: f64, r2: f64, d: f64) -> f64 {
+    if d >= r1 + r2 {
+        return 0.0;
+    }
+    if d <= (r1 - r2).abs() {
+        let r = r1.min(r2);
+        return PI * r * r;
+    }
+    // Each circle contributes a circular segment cut off by the chord
+    // through the two intersection points.
+    let segment = |r: f64, other: f64| {
+        let cos = ((d * d + r * r - other * other) / (2.0 * d * r)).clamp(-1.0, 1.0);
+        r * r * cos.acos()
+    };
+    let kite = (-d + r1 + r2) * (d + r1 - r2) * (d - r1 + r2) * (d + r1 + r2);
+    segment(r1, r2) + segment(r2, r1) - 0.5 * kite.max(0.0).sqrt()
+}
+
+/// The area of a circle inside a simple polygon: the signed sum, over the
+/// polygon's edges, of the circle's overlap with the triangle joining the
+/// edge to the centre.
+fn circle_polygon_area(center: Point, radius: f64, vertices: &[Point]) -> f64 {
+    let relative = |p: Point| Point::new(p.x - center.x, p.y - center.y);
+    let signed: f64 = edges(vertices)
+        .map(|(p, q)| circle_triangle_area(radius, relative(p), relative(q)))
+        .sum();
+    signed.abs()
+}
+
+/// The signed area of the overlap between the circle of radius `r` about
+/// the origin and the triangle with corners at the origin, `a` and `b`.
+fn circle_triangle_area(r: f64, a: Point, b: Point) -> f64 {
+    // Split ab where it crosses the circle; each piece then lies wholly
+    // inside the circle (a triangle) or outside it (a sector). A piece is
+    // inside when its whole chord is, that is when both its ends are: an
+    // edge only touching the circle has ends outside it, but its midpoint
+    // lies exactly on it.
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let qa = dx * dx + dy * dy;
+    let mut cuts = vec![0.0];
+    if qa > 0.0 {
+        let qb = a.x * dx + a.y * dy;
+        let qc = a.x * a.x + a.y * a.y - r * r;
+        let discriminant = qb * qb - qa * qc;
+        if discriminant > 0.0 {
+            let root = discriminant.sqrt();
+            for t in [(-qb - root) / qa, (-qb + root) / qa] {
+                if t > 0.0 && t < 1.0 {
+                    cuts.push(t);
+                }
+            }
+        }
+    }
+    cuts.push(1.0);
+    let at = |t: f64| Point::new(a.x + t * dx, a.y + t * dy);
+    // The cuts are rounded, so allow for them landing just outside.
+    let within = |p: Point| p.x.hypot(p.y) <= r * (1.0 + 1e-9);
+    cuts.windows(2)
+        .map(|pair| {
+            let (p, q) = (at(pair[0]), at(pair[1]));
+            let cross = p.x * q.y - p.y * q.x;
+            if within(p) && within(q) {
+                cross / 2.0
+            } else {
+                let angle = cross.atan2(p.x * q.x + p.y * q.y);
+                r * r * angle / 2.0
+            }
+        })
+        .sum()
+}
+
+/// The area inside both simple polygons. The second polygon is the signed
+/// sum of the triangles fanning out from its first vertex, and each of
+/// those is convex, so it can clip the first polygon directly.
+fn polygon_overlap_area(subject: &[Point], clip: &[Point]) -> f64 {
+    let orientation_of_clip = signed_area(clip).signum();
+    let fan = clip
+        .windows(2)
+        .skip(1)
+        .map(|pair| [clip[0], pair[0], pair[1]]);
+    let signed: f64 = fan
+        .map(|triangle| {
+            let sign = signed_area(&triangle).signum();
+            if sign == 0.0 {
+                return 0.0;
+            }
+            sign * signed_area(&clip_to_triangle(subject, triangle)).abs()
+        })
+        .sum();
+    (orientation_of_clip * signed).max(0.0)
+}
+
+fn signed_area(vertices: &[Point]) -> f64 {
+    edges(vertices)
+        .map(|(p, q)| p.x * q.y - q.x * p.y)
+        .sum::<f64>()
+        / 2.0
+}
+
+/// Sutherland–Hodgman clipping of `subject` to the inside of `triangle`.
+fn clip_to_triangle(subject: &[Point], mut triangle: [Point; 3]) -> Vec<Point> {
+    if signed_area(&triangle) < 0.0 {
+        triangle.reverse();
+    }
+    let mut output = subject.to_vec();
+    for (c1, c2) in edges(&triangle) {
+        let input = std::mem::take(&mut output);
+        let inside = |p: Point| orientation(c1, c2, p) >= 0.0;
+        let crossing = |p: Point, q: Point| {
+            let (dp, dq) = (orientation(c1, c2, p), orientation(c1, c2, q));
+            let t = dp / (dp - dq);
+            Point::new(p.x + t * (q.x - p.x), p.y + t * (q.y - p.y))
+        };
+        for (p, q) in edges(&input) {
+            match (inside(p), inside(q)) {
+                (true, true) => output.push(q),
+                (true, false) => output.push(crossing(p, q)),
+                (false, true) => {
+                    output.push(crossing(p, q));
+                    output.push(q);
+                }
+                (false, false) => {}
+            }
+        }
+    }
+    output
+}
+
+fn is_convex(vertices: &[Point]) -> bool {
+    let n = vertices.len();
+    let turns =
+        (0..n).map(|i| orientation(vertices[i], vertices[(i + 1) % n], vertices[(i + 2) % n]));
+    let (mut left, mut right) = (false, false);
+    for turn in turns {
+        left |= turn > 0.0;
+        right |= turn < 0.0;
+    }
+    !(left && right)
+}
+
+/// Whether some edge normal of `p` separates the two convex polygons.
+fn has_separating_axis(p: &[Point], q: &[Point]) -> bool {
+    edges(p).any(|(a, b)| {
+        let axis = Point::new(a.y - b.y, b.x - a.x);
+        let project = |vertices: &[Point]| {
+            vertices
+                .iter()
+                .map(|v| v.x * axis.x + v.y * axis.y)
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| {
+                    (lo.min(x), hi.max(x))
+                })
+        };
+        let ((p_lo, p_hi), (q_lo, q_hi)) = (project(p), project(q));
+        p_hi < q_lo || q_hi < p_lo
+    })
+}
+
+/// Whether two simple polygons, convex or not, overlap: either their
+/// edges meet, or one lies wholly inside the other.
+fn polygons_intersect(p: &[Point], q: &[Point]) -> bool {
+    edges(p).any(|(a, b)| edges(q).any(|(c, d)| segments_intersect(a, b, c, d)))
+        || polygon_contains(p, q[0])
+        || polygon_contains(q, p[0])
+}
@@ -0,0 +1,392 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    AnyShape, Circle, Ellipse, Error, Point, Polygon, Rectangle, RegularPolygon, Shape, Square,
+    Triangle, ZeroPolicy,
+};
+
+/// Why text or JSON could not be turned into a shape.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseShapeError {
+    /// Input that doesn't follow the shape syntax.
+    Syntax(String),
+    /// A shape of a different kind than the one being read, such as a
+    /// circle where a `Rectangle` was expected.
+    UnexpectedKind {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// Well-formed input with values the shape's constructor rejects.
+    Invalid(Error),
+}
+
+impl fmt::Display for ParseShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseShapeError::Syntax(message) => f.write_str(message),
+            ParseShapeError::UnexpectedKind { expected, found } => {
+                write!(f, "expected a {}, found a {}", expected, found)
+            }
+            ParseShapeError::Invalid(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ParseShapeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseShapeError::Invalid(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for ParseShapeError {
+    fn from(err: Error) -> Self {
+        ParseShapeError::Invalid(err)
+    }
+}
+
+/// The JSON form of every shape, tagged with its kind:
+/// `{"type":"circle","radius":3}`. The position and zero policy are left
+/// out while they have their default values.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum ShapeRecord {
+    Rectangle {
+        width: f64,
+        height: f64,
+        #[serde(flatten)]
+        placement: Placement,
+    },
+    Circle {
+        radius: f64,
+        #[serde(flatten)]
+        placement: Placement,
+    },
+    Square {
+        side: f64,
+        #[serde(flatten)]
+        placement: Placement,
+    },
+    Triangle {
+        a: f64,
+        b: f64,
+        c: f64,
+        #[serde(flatten)]
+        placement: Placement,
+    },
+    Ellipse {
+        radius_x: f64,
+        radius_y: f64,
+        #[serde(flatten)]
+        placement: Placement,
+    },
+    RegularPolygon {
+        sides: usize,
+        side_length: f64,
+        #[serde(flatten)]
+        placement: Placement,
+    },
+    Polygon {
+        vertices: Vec<Point>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct Placement {
+    #[serde(default, skip_serializing_if = "is_origin")]
+    position: Point,
+    #[serde(default, skip_serializing_if = "is_default_policy")]
+    zero_policy: ZeroPolicy,
+}
+
+fn is_origin(point: &Point) -> bool {
+    *point == Point::default()
+}
+
+fn is_default_policy(policy: &ZeroPolicy) -> bool {
+    *policy == ZeroPolicy::default()
+}
+
+impl AnyShape {
+    /// The name of the kind of shape, as used in JSON and the text syntax.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AnyShape::Rectangle(_) => "rectangle",
+            AnyShape::Circle(_) => "circle",
+            AnyShape::Square(_) => "square",
+            AnyShape::Triangle(_) => "triangle",
+            AnyShape::Ellipse(_) => "ellipse",
+            AnyShape::RegularPolygon(_) => "regular_polygon",
+            AnyShape::Polygon(_) => "polygon",
+        }
+    }
+}
+
+impl From<AnyShape> for ShapeRecord {
+    fn from(shape: AnyShape) -> Self {
+        let placement = |position, zero_policy| Placement {
+            position,
+            zero_policy,
+        };
+        match shape {
+            AnyShape::Rectangle(r) => ShapeRecord::Rectangle {
+                width: r.width,
+                height: r.height,
+                placement: placement(r.position, r.zero_policy),
+            },
+            AnyShape::Circle(c) => ShapeRecord::Circle {
+                radius: c.radius,
+                placement: placement(c.position, c.zero_policy),
+            },
+            AnyShape::Square(s) => ShapeRecord::Square {
+                side: s.side,
+                placement: placement(s.position, s.zero_policy),
+            },
+            AnyShape::Triangle(t) => ShapeRecord::Triangle {
+                a: t.a,
+                b: t.b,
+                c: t.c,
+                placement: placement(t.position, t.zero_policy),
+            },
+            AnyShape::Ellipse(e) => ShapeRecord::Ellipse {
+                radius_x: e.radius_x,
+                radius_y: e.radius_y,
+                placement: placement(e.position, e.zero_policy),
+            },
+            AnyShape::RegularPolygon(p) => ShapeRecord::RegularPolygon {
+                sides: p.sides,
+                side_length: p.side_length,
+                placement: placement(p.position, p.zero_policy),
+            },
+            AnyShape::Polygon(p) => ShapeRecord::Polygon {
+                vertices: p.vertices,
+            },
+        }
+    }
+}
+
+/// Builds the shape through its constructor, so a record with invalid
+/// values fails just as the constructor would.
+impl TryFrom<ShapeRecord> for AnyShape {
+    type Error = ParseShapeError;
+
+    fn try_from(record: ShapeRecord) -> Result<Self, Self::Error> {
+        let shape = match record {
+            ShapeRecord::Rectangle {
+                width,
+                height,
+                placement,
+            } => Rectangle::new(width, height)?
+                .with_zero_policy(placement.zero_policy)?
+                .with_position(placement.position)?
+                .into(),
+            ShapeRecord::Circle { radius, placement } => Circle::new(radius)?
+                .with_zero_policy(placement.zero_policy)?
+                .with_position(placement.position)?
+                .into(),
+            ShapeRecord::Square { side, placement } => Square::new(side)?
+                .with_zero_policy(placement.zero_policy)?
+                .with_position(placement.position)?
+                .into(),
+            ShapeRecord::Triangle { a, b, c, placement } => Triangle::new(a, b, c)?
+                .with_zero_policy(placement.zero_policy)?
+                .with_position(placement.position)?
+                .into(),
+            ShapeRecord::Ellipse {
+                radius_x,
+                radius_y,
+                placement,
+            } => Ellipse::new(radius_x, radius_y)?
+                .with_zero_policy(placement.zero_policy)?
+                .with_position(placement.position)?
+                .into(),
+            ShapeRecord::RegularPolygon {
+                sides,
+                side_length,
+                placement,
+            } => RegularPolygon::new(sides, side_length)?
+                .with_zero_policy(placement.zero_policy)?
+                .with_position(placement.position)?
+                .into(),
+            ShapeRecord::Polygon { vertices } => Polygon::new(vertices)?.into(),
+        };
+        Ok(shape)
+    }
+}
+
+/// Lets each shape serialize to, and deserialize from, the same tagged
+/// JSON as `AnyShape`.
+macro_rules! record_conversions {
+    ($($shape:ident => $kind:literal),*) => {
+        $(
+            impl From<$shape> for ShapeRecord {
+                fn from(shape: $shape) -> Self {
+                    AnyShape::$shape(shape).into()
+                }
+            }
+
+            impl TryFrom<ShapeRecord> for $shape {
+                type Error = ParseShapeError;
+
+                fn try_from(record: ShapeRecord) -> Result<Self, Self::Error> {
+                    match AnyShape::try_from(record)? {
+                        AnyShape::$shape(shape) => Ok(shape),
+                        other => Err(ParseShapeError::UnexpectedKind {
+                            expected: $kind,
+                            found: other.kind(),
+                        }),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+record_conversions!(
+    Rectangle => "rectangle",
+    Circle => "circle",
+    Square => "square",
+    Triangle => "triangle",
+    Ellipse => "ellipse",
+    RegularPolygon => "regular_polygon",
+    Polygon => "polygon"
+);
+
+/// The text syntax read by `FromStr`, one shape per line:
+///
+/// ```text
+/// rect 7x3
+/// square 4
+/// circle r=17
+/// ellipse 3x2
+/// triangle 3,4,5
+/// regular n=6 s=2
+/// polygon 0,0 4,0 0,3
+/// ```
+///
+/// All but `polygon` may end with `at X,Y` to set the position. The zero
+/// policy is not part of the syntax; parsed shapes allow zero.
+impl fmt::Display for AnyShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnyShape::Rectangle(r) => write!(f, "rect {}x{}", r.width, r.height)?,
+            AnyShape::Circle(c) => write!(f, "circle r={}", c.radius)?,
+            AnyShape::Square(s) => write!(f, "square {}", s.side)?,
+            AnyShape::Triangle(t) => write!(f, "triangle {},{},{}", t.a, t.b, t.c)?,
+            AnyShape::Ellipse(e) => write!(f, "ellipse {}x{}", e.radius_x, e.radius_y)?,
+            AnyShape::RegularPolygon(p) => write!(f, "regular n={} s={}", p.sides, p.side_length)?,
+            AnyShape::Polygon(p) => {
+                f.write_str("polygon")?;
+                for vertex in &p.vertices {
+                    write!(f, " {},{}", vertex.x, vertex.y)?;
+                }
+                return Ok(());
+            }
+        }
+        let position = self.position();
+        if !is_origin(&position) {
+            write!(f, " at {},{}", position.x, position.y)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for AnyShape {
+    type Err = ParseShapeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words: Vec<&str> = s.split_whitespace().collect();
+        let Some((&kind, _)) = words.split_first() else {
+            return Err(syntax("expected a shape"));
+        };
+        let position = match words[..] {
+            [.., "at", point] if kind != "polygon" => {
+                words.truncate(words.len() - 2);
+                Some(parse_point(point)?)
+            }
+            _ => None,
+        };
+        let shape: AnyShape = match (kind, &words[1..]) {
+            ("rect" | "rectangle", [size]) => {
+                let (width, height) = parse_pair(size, 'x')?;
+                Rectangle::new(width, height)?.into()
+            }
+            ("square", [side]) => Square::new(parse_number(side)?)?.into(),
+            ("circle", [radius]) => Circle::new(parse_number(keyed(radius, "r")?)?)?.into(),
+            ("ellipse", [radii]) => {
+                let (radius_x, radius_y) = parse_pair(radii, 'x')?;
+                Ellipse::new(radius_x, radius_y)?.into()
+            }
+            ("triangle", [sides]) => match sides.split(',').collect::<Vec<_>>()[..] {
+                [a, b, c] => {
+                    Triangle::new(parse_number(a)?, parse_number(b)?, parse_number(c)?)?.into()
+                }
+                _ => return Err(syntax(format!("expected three sides, found {:?}", sides))),
+            },
+            ("regular", [sides, length]) => {
+                let sides = keyed(sides, "n")?;
+                let sides = sides
+                    .parse()
+                    .map_err(|_| syntax(format!("invalid side count {:?}", sides)))?;
+                RegularPolygon::new(sides, parse_number(keyed(length, "s")?)?)?.into()
+            }
+            ("polygon", vertices) => {
+                let vertices = vertices
+                    .iter()
+                    .map(|v| parse_point(v))
+                    .collect::<Result<_, _>>()?;
+                Polygon::new(vertices)?.into()
+            }
+            (
+                "rect" | "rectangle" | "square" | "circle" | "ellipse" | "triangle" | "regular",
+                _,
+            ) => return Err(syntax(format!("wrong number of values for {}", kind))),
+            _ => return Err(syntax(format!("unknown shape {:?}", kind))),
+        };
+        match (shape, position) {
+            (shape, None) => Ok(shape),
+            (mut shape, Some(position)) => {
+                super::dispatch!(&mut shape, shape => shape.set_position(position)?);
+                Ok(shape)
+            }
+        }
+    }
+}
+
+fn syntax(message: impl Into<String>) -> ParseShapeError {
+    ParseShapeError::Syntax(message.into())
+}
+
+/// NaN and infinities parse, so that the constructors reject them with
+/// `Error::NonFinite` like any other invalid value.
+fn parse_number(word: &str) -> Result<f64, ParseShapeError> {
+    word.parse()
+        .map_err(|_| syntax(format!("invalid number {:?}", word)))
+}
+
+fn parse_pair(word: &str, separator: char) -> Result<(f64, f64), ParseShapeError> {
+    match word.split_once(separator) {
+        Some((x, y)) => Ok((parse_number(x)?, parse_number(y)?)),
+        None => Err(syntax(format!(
+            "expected two numbers separated by {:?}, found {:?}",
+            separator, word
+        ))),
+    }
+}
+
+fn parse_point(word: &str) -> Result<Point, ParseShapeError> {
+    let (x, y) = parse_pair(word, ',')?;
+    Ok(Point::new(x, y))
+}
+
+/// The value of `key=value`.
+fn keyed<'a>(word: &'a str, key: &str) -> Result<&'a str, ParseShapeError> {
+    word.strip_prefix(key)
+        .and_then(|rest| rest.strip_prefix('='))
+        .ok_or_else(|| syntax(format!("expected {}=..., found {:?}", key, word)))
+}
@@ -0,0 +1,329 @@
+use std::f64::consts::PI;
+
+use super::{check_dimension, Error, Shape, ZeroPolicy};
+
+pub trait Solid {
+    fn volume(&self) -> f64;
+    fn surface_area(&self) -> f64;
+}
+
+/// A rectangular box of the given width, height and depth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cuboid {
+    width: f64,
+    height: f64,
+    depth: f64,
+    zero_policy: ZeroPolicy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sphere {
+    radius: f64,
+    zero_policy: ZeroPolicy,
+}
+
+/// A right circular cylinder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cylinder {
+    radius: f64,
+    height: f64,
+    zero_policy: ZeroPolicy,
+}
+
+/// A right circular cone, measured from the centre of its base to its apex.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cone {
+    radius: f64,
+    height: f64,
+    zero_policy: ZeroPolicy,
+}
+
+/// A prism made by sweeping a 2D `base` shape straight up by `height`.
+/// Extruding a `Circle` gives a cylinder and a `Rectangle` a cuboid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extrusion<S> {
+    base: S,
+    height: f64,
+    zero_policy: ZeroPolicy,
+}
+
+impl Cuboid {
+    pub fn new(width: f64, height: f64, depth: f64) -> Result<Self, Error> {
+        let cuboid = Self {
+            width,
+            height,
+            depth,
+            zero_policy: ZeroPolicy::Allow,
+        };
+        cuboid.validate()?;
+        Ok(cuboid)
+    }
+    /// Applies `zero_policy` to the current and any later dimensions.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
+    }
+    pub fn set_width(&mut self, width: f64) -> Result<(), Error> {
+        check_dimension(width, "width", Error::InvalidWidth, self.zero_policy)?;
+        self.width = width;
+        Ok(())
+    }
+    pub fn set_height(&mut self, height: f64) -> Result<(), Error> {
+        check_dimension(height, "height", Error::InvalidHeight, self.zero_policy)?;
+        self.height = height;
+        Ok(())
+    }
+    pub fn set_depth(&mut self, depth: f64) -> Result<(), Error> {
+        check_dimension(depth, "depth", Error::InvalidDepth, self.zero_policy)?;
+        self.depth = depth;
+        Ok(())
+    }
+    pub fn get_width(&self) -> f64 {
+        self.width
+    }
+    pub fn get_height(&self) -> f64 {
+        self.height
+    }
+    pub fn get_depth(&self) -> f64 {
+        self.depth
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_dimension(self.width, "width", Error::InvalidWidth, self.zero_policy)?;
+        check_dimension(
+            self.height,
+            "height",
+            Error::InvalidHeight,
+            self.zero_policy,
+        )?;
+        check_dimension(self.depth, "depth", Error::InvalidDepth, self.zero_policy)
+    }
+}
+
+impl Solid for Cuboid {
+    fn volume(&self) -> f64 {
+        self.width * self.height * self.depth
+    }
+
+    fn surface_area(&self) -> f64 {
+        let (w, h, d) = (self.width, self.height, self.depth);
+        2.0 * (w * h + h * d + d * w)
+    }
+}
+
+impl Sphere {
+    pub fn new(radius: f64) -> Result<Self, Error> {
+        let sphere = Self {
+            radius,
+            zero_policy: ZeroPolicy::Allow,
+        };
+        sphere.validate()?;
+        Ok(sphere)
+    }
+    /// Applies `zero_policy` to the current and any later radius.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
+    }
+    pub fn set_radius(&mut self, radius: f64) -> Result<(), Error> {
+        check_dimension(radius, "radius", Error::InvalidRadius, self.zero_policy)?;
+        self.radius = radius;
+        Ok(())
+    }
+    pub fn get_radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_dimension(
+            self.radius,
+            "radius",
+            Error::InvalidRadius,
+            self.zero_policy,
+        )
+    }
+}
+
+impl Solid for Sphere {
+    fn volume(&self) -> f64 {
+        4.0 / 3.0 * PI * self.radius.powi(3)
+    }
+
+    fn surface_area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+}
+
+impl Cylinder {
+    pub fn new(radius: f64, height: f64) -> Result<Self, Error> {
+        let cylinder = Self {
+            radius,
+            height,
+            zero_policy: ZeroPolicy::Allow,
+        };
+        cylinder.validate()?;
+        Ok(cylinder)
+    }
+    /// Applies `zero_policy` to the current and any later dimensions.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
+    }
+    pub fn set_radius(&mut self, radius: f64) -> Result<(), Error> {
+        check_dimension(radius, "radius", Error::InvalidRadius, self.zero_policy)?;
+        self.radius = radius;
+        Ok(())
+    }
+    pub fn set_height(&mut self, height: f64) -> Result<(), Error> {
+        check_dimension(height, "height", Error::InvalidHeight, self.zero_policy)?;
+        self.height = height;
+        Ok(())
+    }
+    pub fn get_radius(&self) -> f64 {
+        self.radius
+    }
+    pub fn get_height(&self) -> f64 {
+        self.height
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_dimension(
+            self.radius,
+            "radius",
+            Error::InvalidRadius,
+            self.zero_policy,
+        )?;
+        check_dimension(
+            self.height,
+            "height",
+            Error::InvalidHeight,
+            self.zero_policy,
+        )
+    }
+}
+
+impl Solid for Cylinder {
+    fn volume(&self) -> f64 {
+        PI * self.radius * self.radius * self.height
+    }
+
+    fn surface_area(&self) -> f64 {
+        2.0 * PI * self.radius * (self.radius + self.height)
+    }
+}
+
+impl Cone {
+    pub fn new(radius: f64, height: f64) -> Result<Self, Error> {
+        let cone = Self {
+            radius,
+            height,
+            zero_policy: ZeroPolicy::Allow,
+        };
+        cone.validate()?;
+        Ok(cone)
+    }
+    /// Applies `zero_policy` to the current and any later dimensions.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
+    }
+    pub fn set_radius(&mut self, radius: f64) -> Result<(), Error> {
+        check_dimension(radius, "radius", Error::InvalidRadius, self.zero_policy)?;
+        self.radius = radius;
+        Ok(())
+    }
+    pub fn set_height(&mut self, height: f64) -> Result<(), Error> {
+        check_dimension(height, "height", Error::InvalidHeight, self.zero_policy)?;
+        self.height = height;
+        Ok(())
+    }
+    pub fn get_radius(&self) -> f64 {
+        self.radius
+    }
+    pub fn get_height(&self) -> f64 {
+        self.height
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_dimension(
+            self.radius,
+            "radius",
+            Error::InvalidRadius,
+            self.zero_policy,
+        )?;
+        check_dimension(
+            self.height,
+            "height",
+            Error::InvalidHeight,
+            self.zero_policy,
+        )
+    }
+}
+
+impl Solid for Cone {
+    fn volume(&self) -> f64 {
+        PI * self.radius * self.radius * self.height / 3.0
+    }
+
+    // The base plus the lateral surface, which unrolls into a sector of a
+    // circle whose radius is the slant height.
+    fn surface_area(&self) -> f64 {
+        let slant = self.radius.hypot(self.height);
+        PI * self.radius * (self.radius + slant)
+    }
+}
+
+impl<S: Shape> Extrusion<S> {
+    pub fn new(base: S, height: f64) -> Result<Self, Error> {
+        let extrusion = Self {
+            base,
+            height,
+            zero_policy: ZeroPolicy::Allow,
+        };
+        extrusion.validate()?;
+        Ok(extrusion)
+    }
+    /// Applies `zero_policy` to the current and any later height. The
+    /// base keeps its own policy.
+    pub fn with_zero_policy(mut self, zero_policy: ZeroPolicy) -> Result<Self, Error> {
+        self.zero_policy = zero_policy;
+        self.validate()?;
+        Ok(self)
+    }
+    pub fn set_height(&mut self, height: f64) -> Result<(), Error> {
+        check_dimension(height, "height", Error::InvalidHeight, self.zero_policy)?;
+        self.height = height;
+        Ok(())
+    }
+    pub fn get_base(&self) -> &S {
+        &self.base
+    }
+    pub fn get_height(&self) -> f64 {
+        self.height
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_dimension(
+            self.height,
+            "height",
+            Error::InvalidHeight,
+            self.zero_policy,
+        )
+    }
+}
+
+impl<S: Shape> Solid for Extrusion<S> {
+    fn volume(&self) -> f64 {
+        self.base.area() * self.height
+    }
+
+    // The two caps plus the sides, which unroll into a rectangle as long
+    // as the base's perimeter.
+    fn surface_area(&self) -> f64 {
+        2.0 * self.base.area() + self.base.perimeter() * self.height
+    }
+}
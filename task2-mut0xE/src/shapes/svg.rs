@@ -0,0 +1,187 @@
+use std::fmt::Write;
+
+use super::collision::Geometry;
+use super::{BoundingBox, Point, Shape};
+
+/// How a shape is drawn. Colours are any SVG paint, such as `"red"`,
+/// `"#336699"` or `"none"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    pub fill: String,
+    pub stroke: String,
+    pub stroke_width: f64,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fill: "none".to_string(),
+            stroke: "black".to_string(),
+            stroke_width: 1.0,
+        }
+    }
+}
+
+/// An SVG document of positioned shapes. The view box is fitted to the
+/// bounding boxes of all the shapes plus a margin, and the y axis points up
+/// as it does for shapes, not down as SVG's does.
+///
+/// Numbers are written with at most six decimals, so the same shapes
+/// always render to the same text.
+pub struct Svg<'a> {
+    shapes: Vec<(&'a dyn Shape, Option<Style>)>,
+    style: Style,
+    margin: f64,
+}
+
+impl<'a> Svg<'a> {
+    pub fn new() -> Self {
+        Svg {
+            shapes: Vec::new(),
+            style: Style::default(),
+            margin: 1.0,
+        }
+    }
+    /// Sets the style of shapes added without one of their own.
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+    /// Sets the space left around the shapes, 1 by default.
+    pub fn with_margin(mut self, margin: f64) -> Self {
+        self.margin = margin;
+        self
+    }
+    pub fn add(&mut self, shape: &'a dyn Shape) -> &mut Self {
+        self.shapes.push((shape, None));
+        self
+    }
+    pub fn add_styled(&mut self, shape: &'a dyn Shape, style: Style) -> &mut Self {
+        self.shapes.push((shape, Some(style)));
+        self
+    }
+
+    /// The area the document shows: every shape's bounding box, grown by
+    /// the margin. Without shapes it is the margin around the origin.
+    pub fn view_box(&self) -> BoundingBox {
+        let origin = BoundingBox::new(Point::default(), Point::default());
+        let mut boxes = self.shapes.iter().map(|(shape, _)| shape.bounding_box());
+        let first = boxes.next().unwrap_or(origin);
+        let bounds = boxes.fold(first, |a, b| {
+            BoundingBox::new(
+                Point::new(a.min.x.min(b.min.x), a.min.y.min(b.min.y)),
+                Point::new(a.max.x.max(b.max.x), a.max.y.max(b.max.y)),
+            )
+        });
+        BoundingBox::new(
+            Point::new(bounds.min.x - self.margin, bounds.min.y - self.margin),
+            Point::new(bounds.max.x + self.margin, bounds.max.y + self.margin),
+        )
+    }
+
+    pub fn render(&self) -> String {
+        let view = self.view_box();
+        let mut out = String::new();
+        // Writing to a String can't fail.
+        let _ = writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+            number(view.min.x),
+            number(-view.max.y),
+            number(view.width()),
+            number(view.height()),
+            number(view.width()),
+            number(view.height()),
+        );
+        for (shape, style) in &self.shapes {
+            let style = style.as_ref().unwrap_or(&self.style);
+            let _ = writeln!(
+                out,
+                "  {} {}/>",
+                element(&shape.geometry()),
+                attributes(style)
+            );
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+impl Default for Svg<'_> {
+    fn default() -> Self {
+        Svg::new()
+    }
+}
+
+/// Renders `shapes` in the default style.
+pub fn render(shapes: &[&dyn Shape]) -> String {
+    let mut svg = Svg::new();
+    for &shape in shapes {
+        svg.add(shape);
+    }
+    svg.render()
+}
+
+/// The opening of the element drawing `geometry`, without its style. SVG's
+/// y axis points down, so every y coordinate is negated.
+fn element(geometry: &Geometry) -> String {
+    match geometry {
+        Geometry::Box(bounds) => format!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}""#,
+            number(bounds.min.x),
+            number(-bounds.max.y),
+            number(bounds.width()),
+            number(bounds.height())
+        ),
+        Geometry::Circle { center, radius } => format!(
+            r#"<circle cx="{}" cy="{}" r="{}""#,
+            number(center.x),
+            number(-center.y),
+            number(*radius)
+        ),
+        Geometry::Ellipse {
+            center,
+            radius_x,
+            radius_y,
+        } => format!(
+            r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}""#,
+            number(center.x),
+            number(-center.y),
+            number(*radius_x),
+            number(*radius_y)
+        ),
+        Geometry::Polygon(vertices) => {
+            let points: Vec<String> = vertices
+                .iter()
+                .map(|p| format!("{},{}", number(p.x), number(-p.y)))
+                .collect();
+            format!(r#"<polygon points="{}""#, points.join(" "))
+        }
+    }
+}
+
+fn attributes(style: &Style) -> String {
+    format!(
+        r#"fill="{}" stroke="{}" stroke-width="{}""#,
+        escape(&style.fill),
+        escape(&style.stroke),
+        number(style.stroke_width)
+    )
+}
+
+/// `value` with at most six decimals and no trailing zeros.
+fn number(value: f64) -> String {
+    let text = format!("{:.6}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
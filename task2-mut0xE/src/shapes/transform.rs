@@ -0,0 +1,326 @@
+use std::f64::consts::FRAC_PI_2;
+
+use super::{
+    dispatch, AnyShape, Circle, Ellipse, Error, Point, Polygon, Rectangle, RegularPolygon, Shape,
+    Square, Triangle,
+};
+
+/// A 2D affine transform, mapping `(x, y)` to
+/// `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    pub fn translation(dx: f64, dy: f64) -> Self {
+        Affine {
+            e: dx,
+            f: dy,
+            ..Affine::IDENTITY
+        }
+    }
+
+    /// Scales by `sx` horizontally and `sy` vertically about the origin.
+    pub fn scaling(sx: f64, sy: f64) -> Self {
+        Affine {
+            a: sx,
+            d: sy,
+            ..Affine::IDENTITY
+        }
+    }
+
+    /// Rotates counter-clockwise by `angle` radians about the origin.
+    /// Multiples of a quarter turn are exact, so that rotating a rectangle
+    /// by one still gives a rectangle.
+    pub fn rotation(angle: f64) -> Self {
+        let quarter_turns = angle / FRAC_PI_2;
+        let (sin, cos) = if quarter_turns == quarter_turns.round() {
+            match quarter_turns.rem_euclid(4.0) as u8 {
+                0 => (0.0, 1.0),
+                1 => (1.0, 0.0),
+                2 => (0.0, -1.0),
+                _ => (-1.0, 0.0),
+            }
+        } else {
+            angle.sin_cos()
+        };
+        Affine {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            ..Affine::IDENTITY
+        }
+    }
+
+    /// The transform that applies `self` and then `next`.
+    pub fn then(&self, next: &Affine) -> Affine {
+        Affine {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    /// `self` applied about `center` instead of the origin.
+    fn about(&self, center: Point) -> Affine {
+        Affine::translation(-center.x, -center.y)
+            .then(self)
+            .then(&Affine::translation(center.x, center.y))
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        Point::new(
+            self.a * point.x + self.c * point.y + self.e,
+            self.b * point.x + self.d * point.y + self.f,
+        )
+    }
+
+    /// Zero when the transform flattens the plane onto a line or a point.
+    fn determinant(&self) -> f64 {
+        self.a * self.d - self.b * self.c
+    }
+
+    fn is_finite(&self) -> bool {
+        [self.a, self.b, self.c, self.d, self.e, self.f]
+            .iter()
+            .all(|v| v.is_finite())
+    }
+
+    /// Whether axis-aligned boxes map to axis-aligned boxes: the transform
+    /// only scales, reflects and turns by quarter turns.
+    fn is_axis_aligned(&self) -> bool {
+        (self.b == 0.0 && self.c == 0.0) || (self.a == 0.0 && self.d == 0.0)
+    }
+
+    /// The scale factor of a transform that only scales uniformly and
+    /// translates, keeping shapes the same way up.
+    fn uniform_scale(&self) -> Option<f64> {
+        (self.b == 0.0 && self.c == 0.0 && self.a == self.d && self.a >= 0.0).then_some(self.a)
+    }
+
+    /// The scale factor of a transform that preserves angles: a uniform
+    /// scale combined with any rotation or reflection.
+    fn similarity_scale(&self) -> Option<f64> {
+        let rotates = self.a == self.d && self.b == -self.c;
+        let reflects = self.a == -self.d && self.b == self.c;
+        (rotates || reflects).then(|| self.a.hypot(self.b))
+    }
+
+    /// How much the transform stretches horizontal and vertical lengths,
+    /// for an axis-aligned transform; quarter turns swap the two.
+    fn axis_scales(&self) -> (f64, f64) {
+        if self.b == 0.0 && self.c == 0.0 {
+            (self.a.abs(), self.d.abs())
+        } else {
+            (self.c.abs(), self.b.abs())
+        }
+    }
+}
+
+/// Moving, resizing and rotating shapes. The result is a new shape that
+/// keeps the zero policy of the original, and may be a different kind of
+/// shape: a rectangle rotated by anything other than a quarter turn
+/// becomes a `Polygon`, and a circle stretched along one axis an `Ellipse`.
+pub trait Transform: Shape {
+    /// Maps every point `p` of the shape to `affine.apply(p)`.
+    fn transform(&self, affine: &Affine) -> Result<AnyShape, Error>;
+
+    fn translate(&self, dx: f64, dy: f64) -> Result<AnyShape, Error> {
+        if !dx.is_finite() || !dy.is_finite() {
+            return Err(Error::NonFinite { field: "offset" });
+        }
+        self.transform(&Affine::translation(dx, dy))
+    }
+
+    /// Scales the shape by `factor` about its position.
+    fn scale(&self, factor: f64) -> Result<AnyShape, Error> {
+        if !factor.is_finite() {
+            return Err(Error::NonFinite { field: "factor" });
+        }
+        if factor < 0.0 {
+            return Err(Error::InvalidScale);
+        }
+        self.transform(&Affine::scaling(factor, factor).about(self.position()))
+    }
+
+    /// Rotates the shape counter-clockwise by `angle` radians about its
+    /// position.
+    fn rotate(&self, angle: f64) -> Result<AnyShape, Error> {
+        if !angle.is_finite() {
+            return Err(Error::NonFinite { field: "angle" });
+        }
+        self.transform(&Affine::rotation(angle).about(self.position()))
+    }
+}
+
+fn check_transform(affine: &Affine) -> Result<(), Error> {
+    if !affine.is_finite() {
+        return Err(Error::NonFinite { field: "transform" });
+    }
+    Ok(())
+}
+
+/// The polygon through the images of `vertices`, the corners of a simple
+/// polygon enclosing `area`. A transform that doesn't flatten the plane
+/// keeps a simple polygon simple, so the result needs no new check. A
+/// shape without area, or a flattened one, makes no polygon.
+fn transform_vertices(vertices: &[Point], area: f64, affine: &Affine) -> Result<AnyShape, Error> {
+    if area == 0.0 || affine.determinant() == 0.0 {
+        return Err(Error::UnsupportedTransform);
+    }
+    let vertices: Vec<Point> = vertices.iter().map(|&p| affine.apply(p)).collect();
+    if !vertices.iter().all(|p| p.is_finite()) {
+        return Err(Error::NonFinite { field: "vertex" });
+    }
+    Ok(AnyShape::Polygon(Polygon { vertices }))
+}
+
+/// The corners of an axis-aligned box from `min` to `max`, in
+/// counter-clockwise order.
+fn corners(min: Point, max: Point) -> [Point; 4] {
+    [min, Point::new(max.x, min.y), max, Point::new(min.x, max.y)]
+}
+
+impl Transform for Rectangle {
+    fn transform(&self, affine: &Affine) -> Result<AnyShape, Error> {
+        check_transform(affine)?;
+        let bounds = self.bounding_box();
+        if !affine.is_axis_aligned() {
+            return transform_vertices(&corners(bounds.min, bounds.max), self.area(), affine);
+        }
+        let (p, q) = (affine.apply(bounds.min), affine.apply(bounds.max));
+        let rectangle = Rectangle::new((q.x - p.x).abs(), (q.y - p.y).abs())?
+            .with_zero_policy(self.zero_policy)?
+            .with_position(Point::new(p.x.min(q.x), p.y.min(q.y)))?;
+        Ok(AnyShape::Rectangle(rectangle))
+    }
+}
+
+impl Transform for Square {
+    fn transform(&self, affine: &Affine) -> Result<AnyShape, Error> {
+        check_transform(affine)?;
+        let bounds = self.bounding_box();
+        if !affine.is_axis_aligned() {
+            return transform_vertices(&corners(bounds.min, bounds.max), self.area(), affine);
+        }
+        let (p, q) = (affine.apply(bounds.min), affine.apply(bounds.max));
+        let (width, height) = ((q.x - p.x).abs(), (q.y - p.y).abs());
+        let position = Point::new(p.x.min(q.x), p.y.min(q.y));
+        if width == height {
+            let square = Square::new(width)?
+                .with_zero_policy(self.zero_policy)?
+                .with_position(position)?;
+            return Ok(AnyShape::Square(square));
+        }
+        let rectangle = Rectangle::new(width, height)?
+            .with_zero_policy(self.zero_policy)?
+            .with_position(position)?;
+        Ok(AnyShape::Rectangle(rectangle))
+    }
+}
+
+impl Transform for Circle {
+    fn transform(&self, affine: &Affine) -> Result<AnyShape, Error> {
+        check_transform(affine)?;
+        let center = affine.apply(self.position);
+        if let Some(scale) = affine.similarity_scale() {
+            let circle = Circle::new(scale * self.radius)?
+                .with_zero_policy(self.zero_policy)?
+                .with_position(center)?;
+            return Ok(AnyShape::Circle(circle));
+        }
+        if !affine.is_axis_aligned() {
+            return Err(Error::UnsupportedTransform);
+        }
+        let (sx, sy) = affine.axis_scales();
+        let ellipse = Ellipse::new(sx * self.radius, sy * self.radius)?
+            .with_zero_policy(self.zero_policy)?
+            .with_position(center)?;
+        Ok(AnyShape::Ellipse(ellipse))
+    }
+}
+
+impl Transform for Ellipse {
+    fn transform(&self, affine: &Affine) -> Result<AnyShape, Error> {
+        check_transform(affine)?;
+        let center = affine.apply(self.position);
+        let (radius_x, radius_y) = if affine.is_axis_aligned() {
+            let (sx, sy) = affine.axis_scales();
+            // A quarter turn swaps which radius lies along which axis.
+            if affine.b == 0.0 && affine.c == 0.0 {
+                (sx * self.radius_x, sy * self.radius_y)
+            } else {
+                (sx * self.radius_y, sy * self.radius_x)
+            }
+        } else {
+            match affine.similarity_scale() {
+                Some(scale) if self.radius_x == self.radius_y => {
+                    (scale * self.radius_x, scale * self.radius_y)
+                }
+                _ => return Err(Error::UnsupportedTransform),
+            }
+        };
+        let ellipse = Ellipse::new(radius_x, radius_y)?
+            .with_zero_policy(self.zero_policy)?
+            .with_position(center)?;
+        Ok(AnyShape::Ellipse(ellipse))
+    }
+}
+
+impl Transform for Triangle {
+    fn transform(&self, affine: &Affine) -> Result<AnyShape, Error> {
+        check_transform(affine)?;
+        let Some(scale) = affine.uniform_scale() else {
+            return transform_vertices(&self.vertices(), self.area(), affine);
+        };
+        let triangle = Triangle::new(scale * self.a, scale * self.b, scale * self.c)?
+            .with_zero_policy(self.zero_policy)?
+            .with_position(affine.apply(self.position))?;
+        Ok(AnyShape::Triangle(triangle))
+    }
+}
+
+impl Transform for RegularPolygon {
+    fn transform(&self, affine: &Affine) -> Result<AnyShape, Error> {
+        check_transform(affine)?;
+        let Some(scale) = affine.uniform_scale() else {
+            return transform_vertices(&self.vertices(), self.area(), affine);
+        };
+        let polygon = RegularPolygon::new(self.sides, scale * self.side_length)?
+            .with_zero_policy(self.zero_policy)?
+            .with_position(affine.apply(self.position))?;
+        Ok(AnyShape::RegularPolygon(polygon))
+    }
+}
+
+impl Transform for Polygon {
+    fn transform(&self, affine: &Affine) -> Result<AnyShape, Error> {
+        check_transform(affine)?;
+        transform_vertices(&self.vertices, self.area(), affine)
+    }
+}
+
+impl Transform for AnyShape {
+    fn transform(&self, affine: &Affine) -> Result<AnyShape, Error> {
+        dispatch!(self, shape => shape.transform(affine))
+    }
+}
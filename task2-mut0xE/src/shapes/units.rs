@@ -0,0 +1,208 @@
+use std::fmt;
+
+use super::transform::{Affine, Transform};
+use super::{AnyShape, Circle, Error, Rectangle, Shape};
+
+/// Units of length shapes can be measured in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Millimeter,
+    Centimeter,
+    Meter,
+    Inch,
+    Foot,
+}
+
+/// What to do when a computation combines quantities in different units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnitPolicy {
+    /// Convert the right-hand quantity into the unit of the left.
+    #[default]
+    Convert,
+    /// Fail with `Error::UnitMismatch`.
+    Reject,
+}
+
+/// A length: `value` in `unit`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Length {
+    value: f64,
+    unit: Unit,
+}
+
+/// An area: `value` in square `unit`s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    value: f64,
+    unit: Unit,
+}
+
+/// A shape whose dimensions and position are in `unit`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measured<S> {
+    shape: S,
+    unit: Unit,
+}
+
+impl Unit {
+    /// The length of one of this unit in meters. The imperial units are
+    /// defined exactly in terms of the meter.
+    pub fn in_meters(self) -> f64 {
+        match self {
+            Unit::Millimeter => 0.001,
+            Unit::Centimeter => 0.01,
+            Unit::Meter => 1.0,
+            Unit::Inch => 0.0254,
+            Unit::Foot => 0.3048,
+        }
+    }
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Millimeter => "mm",
+            Unit::Centimeter => "cm",
+            Unit::Meter => "m",
+            Unit::Inch => "in",
+            Unit::Foot => "ft",
+        }
+    }
+    pub fn is_metric(self) -> bool {
+        matches!(self, Unit::Millimeter | Unit::Centimeter | Unit::Meter)
+    }
+
+    /// How many of `unit` make one of this unit.
+    fn factor_to(self, unit: Unit) -> f64 {
+        if self == unit {
+            1.0
+        } else {
+            self.in_meters() / unit.in_meters()
+        }
+    }
+
+    /// Checks that `other` may be combined with quantities in this unit.
+    fn check_mixing(self, other: Unit, policy: UnitPolicy) -> Result<(), Error> {
+        if self != other && policy == UnitPolicy::Reject {
+            return Err(Error::UnitMismatch {
+                left: self,
+                right: other,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+impl Length {
+    pub fn new(value: f64, unit: Unit) -> Self {
+        Self { value, unit }
+    }
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+    /// The same length in `unit`.
+    pub fn to(&self, unit: Unit) -> Length {
+        Length::new(self.value * self.unit.factor_to(unit), unit)
+    }
+    /// The sum of both lengths, in the unit of `self`.
+    pub fn plus(&self, other: Length, policy: UnitPolicy) -> Result<Length, Error> {
+        self.unit.check_mixing(other.unit, policy)?;
+        Ok(Length::new(
+            self.value + other.to(self.unit).value,
+            self.unit,
+        ))
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.value, self.unit)
+    }
+}
+
+impl Area {
+    pub fn new(value: f64, unit: Unit) -> Self {
+        Self { value, unit }
+    }
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+    /// The same area in square `unit`s.
+    pub fn to(&self, unit: Unit) -> Area {
+        Area::new(self.value * self.unit.factor_to(unit).powi(2), unit)
+    }
+    /// The sum of both areas, in the unit of `self`.
+    pub fn plus(&self, other: Area, policy: UnitPolicy) -> Result<Area, Error> {
+        self.unit.check_mixing(other.unit, policy)?;
+        Ok(Area::new(self.value + other.to(self.unit).value, self.unit))
+    }
+}
+
+impl fmt::Display for Area {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}²", self.value, self.unit)
+    }
+}
+
+impl<S: Shape> Measured<S> {
+    pub fn new(shape: S, unit: Unit) -> Self {
+        Self { shape, unit }
+    }
+    pub fn shape(&self) -> &S {
+        &self.shape
+    }
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+    pub fn area(&self) -> Area {
+        Area::new(self.shape.area(), self.unit)
+    }
+    pub fn perimeter(&self) -> Length {
+        Length::new(self.shape.perimeter(), self.unit)
+    }
+    /// The area covered by both shapes, in the unit of `self`.
+    pub fn intersection_area<T: Transform>(
+        &self,
+        other: &Measured<T>,
+        policy: UnitPolicy,
+    ) -> Result<Area, Error> {
+        self.unit.check_mixing(other.unit, policy)?;
+        let other = other.to(self.unit)?;
+        let area = self.shape.intersection_area(&other.shape)?;
+        Ok(Area::new(area, self.unit))
+    }
+}
+
+impl<S: Transform> Measured<S> {
+    /// The same shape measured in `unit`, which scales its dimensions and
+    /// its position alike.
+    pub fn to(&self, unit: Unit) -> Result<Measured<AnyShape>, Error> {
+        let factor = self.unit.factor_to(unit);
+        let shape = self.shape.transform(&Affine::scaling(factor, factor))?;
+        Ok(Measured::new(shape, unit))
+    }
+}
+
+impl Measured<Rectangle> {
+    /// A rectangle in the unit of `width`; `height` is converted to it.
+    pub fn rectangle(width: Length, height: Length) -> Result<Self, Error> {
+        let height = height.to(width.unit);
+        let rectangle = Rectangle::new(width.value, height.value)?;
+        Ok(Measured::new(rectangle, width.unit))
+    }
+}
+
+impl Measured<Circle> {
+    pub fn circle(radius: Length) -> Result<Self, Error> {
+        Ok(Measured::new(Circle::new(radius.value)?, radius.unit))
+    }
+}
@@ -1,5 +1,10 @@
 #[cfg(test)]
 mod calculator_tests {
+    use crate::calculator::expression::*;
+    use crate::calculator::format::*;
+    use crate::calculator::number::*;
+    use crate::calculator::persist::*;
+    use crate::calculator::query::*;
     use crate::calculator::*;
     use rand::Rng;
 
@@ -260,13 +265,1384 @@ mod calculator_tests {
         calculator.subtraction(20, 7);
         assert_eq!(calculator.show_history(), "0: 20 - 7 = 13\n");
     }
+
+    #[test]
+    fn evaluate_precedence_and_parentheses() {
+        let mut calculator = Calculator::new();
+
+        assert_eq!(calculator.evaluate("1 + 2 * 3"), Ok(7));
+        assert_eq!(calculator.evaluate("(3 + 4) * -2 - 7"), Ok(-21));
+        assert_eq!(calculator.evaluate("10 - 4 - 3"), Ok(3));
+        assert_eq!(calculator.evaluate("-(2 + 3)"), Ok(-5));
+        assert_eq!(calculator.evaluate("-9223372036854775808"), Ok(i64::MIN));
+    }
+
+    #[test]
+    fn evaluate_records_steps() {
+        let mut calculator = Calculator::new();
+
+        assert_eq!(calculator.evaluate("(3 + 4) * -2 - 7"), Ok(-21));
+
+        let expected = "0: 3 + 4 = 7\n1: 7 * -2 = -14\n2: -14 - 7 = -21\n";
+        assert_eq!(calculator.show_history(), expected);
+    }
+
+    #[test]
+    fn evaluate_parse_errors() {
+        let mut calculator = Calculator::new();
+
+        let err = calculator.evaluate("3 + $").unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::UnexpectedChar('$'),
+                span: 4..5,
+            })
+        );
+
+        let err = calculator.evaluate("(1 + 2").unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::UnclosedParen,
+                span: 0..1,
+            })
+        );
+
+        let err = calculator.evaluate("1 +").unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::UnexpectedEnd,
+                span: 3..3,
+            })
+        );
+
+        let err = calculator.evaluate("2 3").unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::UnexpectedToken,
+                span: 2..3,
+            })
+        );
+
+        let err = calculator.evaluate("9223372036854775808").unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::NumberTooLarge,
+                span: 0..19,
+            })
+        );
+
+        assert_eq!(calculator.show_history(), "");
+    }
+
+    #[test]
+    fn evaluate_rejects_deep_nesting() {
+        let mut calculator = Calculator::new();
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+
+        assert_eq!(calculator.evaluate(&nested(MAX_DEPTH)), Ok(1));
+        let err = calculator.evaluate(&nested(3000)).unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::TooDeep,
+                span: MAX_DEPTH..MAX_DEPTH + 1,
+            })
+        );
+
+        assert_eq!(
+            calculator.evaluate(&"-".repeat(MAX_DEPTH - 1)),
+            Err(CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::UnexpectedEnd,
+                span: MAX_DEPTH - 1..MAX_DEPTH - 1,
+            }))
+        );
+        assert_eq!(
+            calculator.evaluate(&format!("{}1", "-".repeat(MAX_DEPTH))),
+            Ok(1)
+        );
+        let err = calculator
+            .evaluate(&format!("{}1", "-".repeat(6000)))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::TooDeep,
+                span: MAX_DEPTH..MAX_DEPTH + 1,
+            })
+        );
+        // Long chains of operators are flat, not nested.
+        let sum = vec!["1"; 20000].join(" + ");
+        assert_eq!(calculator.evaluate(&sum), Ok(20000));
+
+        let tower = vec!["1"; 3000].join(" ^ ");
+        assert!(matches!(
+            calculator.evaluate(&tower),
+            Err(CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::TooDeep,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn evaluate_overflow_is_atomic() {
+        let mut calculator = Calculator::new();
+
+        let err = calculator
+            .evaluate("1 + 9223372036854775807 * 2")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::Overflow {
+                op: OperationType::Multiplication,
+                lhs: i64::MAX,
+                rhs: 2,
+            }
+        );
+        assert_eq!(calculator.show_history(), "");
+    }
+
+    #[test]
+    fn extended_operation_type_get_sign() {
+        assert_eq!(OperationType::Division.get_sign(), "/");
+        assert_eq!(OperationType::Remainder.get_sign(), "%");
+        assert_eq!(OperationType::Power.get_sign(), "^");
+        assert_eq!(OperationType::Negate.get_sign(), "neg");
+    }
+
+    #[test]
+    fn extended_operation_type_perform() {
+        assert_eq!(OperationType::Division.perform(17, 5), Some(3));
+        assert_eq!(OperationType::Division.perform(-17, 5), Some(-3));
+        assert_eq!(OperationType::Remainder.perform(17, 5), Some(2));
+        assert_eq!(OperationType::Remainder.perform(-17, 5), Some(-2));
+        assert_eq!(OperationType::Power.perform(3, 4), Some(81));
+        assert_eq!(OperationType::Power.perform(-2, 3), Some(-8));
+        assert_eq!(OperationType::Power.perform(7, 0), Some(1));
+        assert_eq!(OperationType::Negate.perform(5, 0), Some(-5));
+
+        // Test failure cases
+        assert_eq!(OperationType::Division.perform(1, 0), None);
+        assert_eq!(OperationType::Division.perform(i64::MIN, -1), None);
+        assert_eq!(OperationType::Remainder.perform(1, 0), None);
+        assert_eq!(OperationType::Remainder.perform(i64::MIN, -1), None);
+        assert_eq!(OperationType::Power.perform(2, 63), None);
+        assert_eq!(OperationType::Power.perform(2, -1), None);
+        assert_eq!(OperationType::Negate.perform(i64::MIN, 0), None);
+    }
+
+    #[test]
+    fn extended_operations_history() {
+        let mut calculator = Calculator::new();
+
+        assert_eq!(calculator.division(20, 3), Some(6));
+        assert_eq!(calculator.remainder(20, 3), Some(2));
+        assert_eq!(calculator.power(2, 10), Some(1024));
+        assert_eq!(calculator.negation(8), Some(-8));
+        assert_eq!(calculator.division(1, 0), None);
+        assert_eq!(calculator.negation(i64::MIN), None);
+
+        let expected = "0: 20 / 3 = 6\n1: 20 % 3 = 2\n2: 2 ^ 10 = 1024\n3: neg 8 = -8\n";
+        assert_eq!(calculator.show_history(), expected);
+
+        assert_eq!(calculator.repeat(3), Some(-8));
+        assert!(calculator.show_history().ends_with("4: neg 8 = -8\n"));
+    }
+
+    #[test]
+    fn evaluate_extended_operators() {
+        let mut calculator = Calculator::new();
+
+        assert_eq!(calculator.evaluate("7 / 2 + 7 % 2"), Ok(4));
+        assert_eq!(calculator.evaluate("2 ^ 3 ^ 2"), Ok(512));
+        assert_eq!(calculator.evaluate("-2 ^ 2"), Ok(-4));
+        assert_eq!(calculator.evaluate("(-2) ^ 2"), Ok(4));
+        assert_eq!(calculator.evaluate("2 * -(1 + 2)"), Ok(-6));
+    }
+
+    #[test]
+    fn evaluate_division_by_zero() {
+        let mut calculator = Calculator::new();
+
+        let err = calculator.evaluate("1 + 4 / (2 - 2)").unwrap_err();
+        assert_eq!(err, CalcError::DivisionByZero);
+        assert_eq!(calculator.show_history(), "");
+    }
+
+    #[test]
+    fn try_operations_report_errors() {
+        let mut calculator = Calculator::new();
+
+        assert_eq!(calculator.try_addition(2, 3), Ok(5));
+        assert_eq!(
+            calculator.try_addition(i64::MAX, 1),
+            Err(CalcError::Overflow {
+                op: OperationType::Addition,
+                lhs: i64::MAX,
+                rhs: 1,
+            })
+        );
+        assert_eq!(
+            calculator.try_subtraction(i64::MIN, 1),
+            Err(CalcError::Overflow {
+                op: OperationType::Subtraction,
+                lhs: i64::MIN,
+                rhs: 1,
+            })
+        );
+        assert_eq!(
+            calculator.try_division(1, 0),
+            Err(CalcError::DivisionByZero)
+        );
+        assert_eq!(
+            OperationType::Power.try_perform(2, -1),
+            Err(CalcError::InvalidExponent(-1))
+        );
+
+        // Only the successful addition was recorded
+        assert_eq!(calculator.show_history(), "0: 2 + 3 = 5\n");
+    }
+
+    #[test]
+    fn try_repeat_invalid_index() {
+        let mut calculator = Calculator::new();
+        assert_eq!(
+            calculator.try_repeat(0),
+            Err(CalcError::InvalidHistoryIndex { index: 0, len: 0 })
+        );
+
+        calculator.multiplication(3, 4);
+        assert_eq!(calculator.try_repeat(0), Ok(12));
+        assert_eq!(
+            calculator.try_repeat(2),
+            Err(CalcError::InvalidHistoryIndex { index: 2, len: 2 })
+        );
+        assert_eq!(calculator.repeat(2), None);
+    }
+
+    #[test]
+    fn calc_error_display() {
+        let overflow = CalcError::Overflow {
+            op: OperationType::Multiplication,
+            lhs: i64::MAX,
+            rhs: 2,
+        };
+        assert_eq!(
+            overflow.to_string(),
+            "overflow computing 9223372036854775807 * 2"
+        );
+        assert_eq!(
+            CalcError::<i64>::DivisionByZero.to_string(),
+            "division by zero"
+        );
+        assert_eq!(
+            CalcError::<i64>::InvalidHistoryIndex { index: 4, len: 2 }.to_string(),
+            "history index 4 is out of range for 2 entries"
+        );
+
+        let mut calculator = Calculator::new();
+        let err = calculator.evaluate("1 + )").unwrap_err();
+        assert_eq!(err.to_string(), "unexpected token at 4..5");
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn history_entries_store_result_and_id() {
+        let mut calculator = Calculator::new();
+        calculator.addition(2, 3);
+        calculator.evaluate("4 * 5 - 1").unwrap();
+
+        let results: Vec<_> = calculator.history.iter().map(|op| op.result).collect();
+        assert_eq!(results, vec![Some(5), Some(20), Some(19)]);
+
+        let ids: Vec<_> = calculator.history.iter().map(|op| op.id).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert!(calculator.history.iter().all(|op| op.timestamp.is_none()));
+
+        // Ids keep increasing after the history is cleared
+        calculator.clear_history();
+        calculator.subtraction(1, 1);
+        assert_eq!(calculator.history[0].id, 3);
+    }
+
+    #[test]
+    fn repeat_references_source_id() {
+        let mut calculator = Calculator::new();
+        calculator.addition(1, 1);
+        calculator.multiplication(6, 7);
+        calculator.repeat(1);
+        calculator.repeat(2);
+
+        let repeated_from: Vec<_> = calculator
+            .history
+            .iter()
+            .map(|op| op.repeated_from)
+            .collect();
+        assert_eq!(repeated_from, vec![None, None, Some(1), Some(2)]);
+        assert_eq!(calculator.history[3].result, Some(42));
+    }
+
+    #[test]
+    fn record_timestamps() {
+        let mut calculator = Calculator::new();
+        calculator.set_record_timestamps(true);
+
+        let before = std::time::SystemTime::now();
+        calculator.addition(1, 2);
+        let timestamp = calculator.history[0].timestamp.unwrap();
+        assert!(timestamp >= before);
+    }
+
+    #[test]
+    fn show_history_does_not_recompute() {
+        let mut calculator = Calculator::new();
+        calculator
+            .history
+            .push(Operation::new(i64::MAX, 1, OperationType::Addition));
+        assert_eq!(
+            calculator.show_history(),
+            "0: 9223372036854775807 + 1 = overflow\n"
+        );
+    }
+
+    #[test]
+    fn save_and_load_history_json() {
+        let mut calculator = Calculator::new();
+        calculator.addition(-4, 9);
+        calculator.negation(7);
+        calculator.repeat(0);
+
+        let mut buffer = Vec::new();
+        calculator
+            .save_history(&mut buffer, HistoryFormat::Json)
+            .unwrap();
+
+        let mut loaded = Calculator::<i64>::load_history(&buffer[..], HistoryFormat::Json).unwrap();
+        assert_eq!(loaded.history, calculator.history);
+        assert_eq!(loaded.show_history(), calculator.show_history());
+
+        // New entries continue the loaded id sequence
+        loaded.addition(1, 1);
+        assert_eq!(loaded.history[3].id, 3);
+    }
+
+    #[test]
+    fn save_and_load_history_csv() {
+        let mut calculator = Calculator::new();
+        calculator.addition(-4, 9);
+        calculator.negation(7);
+        calculator.power(2, 5);
+
+        let mut buffer = Vec::new();
+        calculator
+            .save_history(&mut buffer, HistoryFormat::Csv)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.clone()).unwrap(),
+            "index,lhs,op,rhs,result\n0,-4,+,9,5\n1,7,neg,,-7\n2,2,^,5,32\n"
+        );
+
+        let loaded = Calculator::<i64>::load_history(&buffer[..], HistoryFormat::Csv).unwrap();
+        assert_eq!(loaded.show_history(), calculator.show_history());
+    }
+
+    #[test]
+    fn load_history_rejects_tampered_rows() {
+        let csv = "index,lhs,op,rhs,result\n0,2,+,2,4\n1,3,*,3,10\n";
+        match Calculator::<i64>::load_history(csv.as_bytes(), HistoryFormat::Csv) {
+            Err(PersistError::ResultMismatch {
+                row,
+                recorded,
+                computed,
+            }) => {
+                assert_eq!(row, 3);
+                assert_eq!(recorded, Some(10));
+                assert_eq!(computed, Some(9));
+            }
+            other => panic!("unexpected result: {:?}", other.err()),
+        }
+
+        let json = r#"[{"first_num":1,"second_num":0,"operation_type":"Division",
+            "result":0,"id":0,"repeated_from":null,"timestamp":null}]"#;
+        assert!(matches!(
+            Calculator::<i64>::load_history(json.as_bytes(), HistoryFormat::Json),
+            Err(PersistError::ResultMismatch { row: 0, .. })
+        ));
+
+        let json = r#"[{"first_num":1,"second_num":1,"operation_type":"Addition",
+            "result":2,"id":18446744073709551615,"repeated_from":null,"timestamp":null}]"#;
+        assert!(matches!(
+            Calculator::<i64>::load_history(json.as_bytes(), HistoryFormat::Json),
+            Err(PersistError::Malformed { row: 0, .. })
+        ));
+
+        // A forged `promoted` flag is recomputed rather than trusted.
+        let json = r#"[{"first_num":1,"second_num":1,"operation_type":"Addition",
+            "result":2,"id":0,"repeated_from":null,"timestamp":null,"promoted":true}]"#;
+        let loaded = Calculator::<i64>::load_history(json.as_bytes(), HistoryFormat::Json).unwrap();
+        assert!(!loaded.history[0].promoted);
+    }
+
+    #[test]
+    fn load_history_rejects_malformed_csv() {
+        let cases = [
+            "lhs,op,rhs\n",
+            "index,lhs,op,rhs,result\n0,1,+,2\n",
+            "index,lhs,op,rhs,result\n1,1,+,2,3\n3,1,+,1,2\n",
+            "index,lhs,op,rhs,result\n-1,1,+,2,3\n",
+            "index,lhs,op,rhs,result\n18446744073709551615,1,+,2,3\n",
+            "index,lhs,op,rhs,result\n0,1,&,2,3\n",
+            "index,lhs,op,rhs,result\n0,one,+,2,3\n",
+        ];
+        for csv in cases {
+            assert!(
+                matches!(
+                    Calculator::<i64>::load_history(csv.as_bytes(), HistoryFormat::Csv),
+                    Err(PersistError::Malformed { .. })
+                ),
+                "{:?} should be rejected",
+                csv
+            );
+        }
+
+        // Rows are numbered by line, starting with the header.
+        let line_of =
+            |csv: &str| match Calculator::<i64>::load_history(csv.as_bytes(), HistoryFormat::Csv) {
+                Err(PersistError::Malformed { row, .. }) => row,
+                other => panic!("unexpected result: {:?}", other.err()),
+            };
+        assert_eq!(line_of("lhs,op,rhs\n"), 1);
+        assert_eq!(line_of("index,lhs,op,rhs,result\n0,1,+,2\n"), 2);
+        assert_eq!(
+            line_of("index,lhs,op,rhs,result\n0,1,+,2,3\n\n1,1,&,2,3\n"),
+            4
+        );
+    }
+
+    #[test]
+    fn undo_and_redo_operations() {
+        let mut calculator = Calculator::new();
+        calculator.addition(1, 2);
+        calculator.repeat(0);
+        calculator.evaluate("2 * 3 + 1").unwrap();
+
+        // Undoing evaluate removes both of its steps at once
+        assert!(calculator.undo());
+        assert_eq!(calculator.show_history(), "0: 1 + 2 = 3\n1: 1 + 2 = 3\n");
+
+        // Take back the mistaken repeat
+        assert!(calculator.undo());
+        assert_eq!(calculator.show_history(), "0: 1 + 2 = 3\n");
+
+        assert!(calculator.redo());
+        assert!(calculator.redo());
+        assert!(!calculator.redo());
+        assert_eq!(
+            calculator.show_history(),
+            "0: 1 + 2 = 3\n1: 1 + 2 = 3\n2: 2 * 3 = 6\n3: 6 + 1 = 7\n"
+        );
+
+        assert!(calculator.undo());
+        assert!(calculator.undo());
+        assert!(calculator.undo());
+        assert!(!calculator.undo());
+        assert_eq!(calculator.show_history(), "");
+    }
+
+    #[test]
+    fn undo_restores_ans() {
+        let mut calculator = Calculator::new();
+        calculator.evaluate("2 + 3").unwrap();
+        calculator.evaluate("ans * 4").unwrap();
+
+        assert!(calculator.undo());
+        assert_eq!(calculator.ans(), Some(&5));
+        assert!(calculator.undo());
+        assert_eq!(calculator.ans(), None);
+        assert_eq!(
+            calculator.evaluate("ans"),
+            Err(CalcError::UnknownVariable("ans".to_string()))
+        );
+
+        assert!(calculator.redo());
+        assert_eq!(calculator.ans(), Some(&5));
+        assert!(calculator.redo());
+        assert_eq!(calculator.evaluate("ans"), Ok(20));
+    }
+
+    #[test]
+    fn undo_clear_history() {
+        let mut calculator = Calculator::new();
+        calculator.addition(1, 2);
+        calculator.subtraction(5, 3);
+        calculator.clear_history();
+        assert_eq!(calculator.show_history(), "");
+
+        assert!(calculator.undo());
+        assert_eq!(calculator.show_history(), "0: 1 + 2 = 3\n1: 5 - 3 = 2\n");
+
+        assert!(calculator.redo());
+        assert_eq!(calculator.show_history(), "");
+    }
+
+    #[test]
+    fn new_operation_discards_redo() {
+        let mut calculator = Calculator::new();
+        calculator.addition(1, 2);
+        calculator.undo();
+        calculator.multiplication(2, 2);
+
+        assert!(!calculator.redo());
+        assert_eq!(calculator.show_history(), "0: 2 * 2 = 4\n");
+    }
+
+    #[test]
+    fn max_undo_depth() {
+        let mut calculator = Calculator::new();
+        calculator.set_max_undo_depth(2);
+        calculator.addition(1, 1);
+        calculator.addition(2, 2);
+        calculator.addition(3, 3);
+
+        assert!(calculator.undo());
+        assert!(calculator.undo());
+        assert!(!calculator.undo());
+        assert_eq!(calculator.show_history(), "0: 1 + 1 = 2\n");
+
+        calculator.set_max_undo_depth(0);
+        calculator.addition(4, 4);
+        assert!(!calculator.undo());
+    }
+
+    #[test]
+    fn i128_backend() {
+        let mut calculator = Calculator::<i128>::default();
+        let big = i64::MAX as i128;
+
+        assert_eq!(calculator.multiplication(big, 4), Some(big * 4));
+        assert_eq!(calculator.multiplication(i128::MAX, 2), None);
+        assert_eq!(calculator.evaluate("2 ^ 100"), Ok(1 << 100));
+        assert_eq!(calculator.repeat(0), Some(big * 4));
+        assert_eq!(
+            calculator.show_history(),
+            "0: 9223372036854775807 * 4 = 36893488147419103228\n\
+             1: 2 ^ 100 = 1267650600228229401496703205376\n\
+             2: 9223372036854775807 * 4 = 36893488147419103228\n"
+        );
+    }
+
+    #[test]
+    fn u64_backend() {
+        let mut calculator = Calculator::<u64>::default();
+
+        assert_eq!(calculator.addition(u64::MAX - 1, 1), Some(u64::MAX));
+        assert_eq!(calculator.subtraction(1, 2), None);
+        assert_eq!(calculator.negation(5), None);
+        assert_eq!(
+            calculator.try_negation(5),
+            Err(CalcError::Overflow {
+                op: OperationType::Negate,
+                lhs: 5,
+                rhs: 0,
+            })
+        );
+        assert_eq!(calculator.evaluate("10 - 3"), Ok(7));
+        assert!(calculator.evaluate("-3 + 10").is_err());
+    }
+
+    #[test]
+    fn f64_backend() {
+        let mut calculator = Calculator::<f64>::default();
+
+        assert_eq!(calculator.addition(0.5, 0.25), Some(0.75));
+        assert_eq!(calculator.division(1.0, 4.0), Some(0.25));
+        assert_eq!(calculator.evaluate("1.5 * 4"), Ok(6.0));
+        assert_eq!(
+            calculator.evaluate("2 ^ 0.5"),
+            Err(CalcError::InvalidExponent(0.5))
+        );
+
+        // Non-finite values are errors rather than results
+        assert_eq!(calculator.division(1.0, 0.0), None);
+        assert_eq!(calculator.multiplication(f64::MAX, 2.0), None);
+        assert_eq!(
+            calculator
+                .try_addition(f64::NAN, 1.0)
+                .map_err(|err| err.to_string()),
+            Err("invalid operand NaN".to_string())
+        );
+        assert_eq!(calculator.addition(1.0, f64::INFINITY), None);
+
+        assert_eq!(
+            calculator.show_history(),
+            "0: 0.5 + 0.25 = 0.75\n1: 1.0 / 4.0 = 0.25\n2: 1.5 * 4.0 = 6.0\n"
+        );
+    }
+
+    #[test]
+    fn rational_backend() {
+        let mut calculator = Calculator::<Rational>::default();
+
+        assert_eq!(calculator.evaluate("1/3 + 1/6"), Ok(Rational::new(1, 2)));
+        assert_eq!(
+            calculator.show_history(),
+            "0: 1 / 3 = 1/3\n1: 1 / 6 = 1/6\n2: 1/3 + 1/6 = 1/2\n"
+        );
+
+        let third = Rational::new(1, 3);
+        assert_eq!(
+            calculator.multiplication(third, Rational::from(3)),
+            Some(Rational::from(1))
+        );
+        assert_eq!(
+            calculator.remainder(Rational::new(7, 2), Rational::from(2)),
+            Some(Rational::new(3, 2))
+        );
+        assert_eq!(
+            calculator.power(Rational::new(-2, 3), Rational::from(3)),
+            Some(Rational::new(-8, 27))
+        );
+        assert_eq!(calculator.division(third, Rational::from(0)), None);
+        assert_eq!(
+            calculator.addition(Rational::new(1, i64::MAX), Rational::new(1, i64::MAX - 1)),
+            None
+        );
+        assert_eq!(
+            calculator.evaluate("0.5"),
+            Err(CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::InvalidNumber,
+                span: 0..3,
+            }))
+        );
+    }
+
+    #[test]
+    fn generic_history_round_trip() {
+        let mut calculator = Calculator::<Rational>::default();
+        calculator.evaluate("2/3 - 1").unwrap();
+
+        for format in [HistoryFormat::Json, HistoryFormat::Csv] {
+            let mut buffer = Vec::new();
+            calculator.save_history(&mut buffer, format).unwrap();
+            let loaded = Calculator::<Rational>::load_history(&buffer[..], format).unwrap();
+            assert_eq!(loaded.show_history(), calculator.show_history());
+        }
+    }
+
+    #[test]
+    fn big_integer_promotes_on_overflow() {
+        let mut calculator = Calculator::<BigInteger>::default();
+        let max = BigInteger::from(i64::MAX);
+
+        let sum = calculator
+            .addition(max.clone(), BigInteger::from(1))
+            .unwrap();
+        assert_eq!(sum.to_string(), "9223372036854775808");
+        assert!(calculator.history[0].promoted);
+
+        // Computed from a promoted operand, so promoted too.
+        let back = calculator.subtraction(sum, BigInteger::from(1)).unwrap();
+        assert_eq!(back, max);
+        assert!(calculator.history[1].promoted);
+
+        assert_eq!(
+            calculator.evaluate("2 ^ 100").map(|r| r.to_string()),
+            Ok("1267650600228229401496703205376".to_string())
+        );
+        assert_eq!(
+            calculator.negation(BigInteger::from(i64::MIN)),
+            Some("9223372036854775808".parse().unwrap())
+        );
+        assert_eq!(
+            calculator.show_history(),
+            "0: 9223372036854775807 + 1 = 9223372036854775808\n\
+             1: 9223372036854775808 - 1 = 9223372036854775807\n\
+             2: 2 ^ 100 = 1267650600228229401496703205376\n\
+             3: neg -9223372036854775808 = 9223372036854775808\n"
+        );
+
+        assert_eq!(
+            calculator.try_division(max.clone(), BigInteger::zero()),
+            Err(CalcError::DivisionByZero)
+        );
+        assert_eq!(
+            calculator.try_power(BigInteger::from(10), max.clone()),
+            Err(CalcError::InvalidExponent(max.clone()))
+        );
+
+        let promoted = |calculator: &Calculator<BigInteger>| {
+            calculator.history.last().is_some_and(|op| op.promoted)
+        };
+        calculator.multiplication(max.clone(), BigInteger::from(2));
+        assert!(promoted(&calculator));
+        calculator.subtraction(max.clone(), BigInteger::from(1));
+        assert!(!promoted(&calculator));
+        assert_eq!(
+            calculator.remainder(BigInteger::from(i64::MIN), BigInteger::from(-1)),
+            Some(BigInteger::from(0))
+        );
+        assert!(!promoted(&calculator));
+    }
+
+    #[test]
+    fn big_integer_history_round_trip() {
+        let mut calculator = Calculator::<BigInteger>::default();
+        calculator.evaluate("99999999999999999999 * 3 - 1").unwrap();
+
+        for format in [HistoryFormat::Json, HistoryFormat::Csv] {
+            let mut buffer = Vec::new();
+            calculator.save_history(&mut buffer, format).unwrap();
+            let loaded = Calculator::<BigInteger>::load_history(&buffer[..], format).unwrap();
+            assert_eq!(loaded.history, calculator.history);
+        }
+    }
+
+    #[test]
+    fn repeat_range_and_replay() {
+        let mut calculator = Calculator::new();
+        calculator.addition(1, 2);
+        calculator.multiplication(3, 4);
+        calculator.negation(5);
+
+        assert_eq!(calculator.repeat_range(1..3), Ok(vec![12, -5]));
+        assert_eq!(calculator.history.len(), 5);
+        assert_eq!(calculator.history[3].repeated_from, Some(1));
+        assert_eq!(calculator.history[4].repeated_from, Some(2));
+        assert!(calculator.undo());
+        assert_eq!(calculator.history.len(), 3);
+
+        assert_eq!(
+            calculator.repeat_range(2..4),
+            Err(CalcError::InvalidHistoryRange {
+                start: 2,
+                end: 4,
+                len: 3,
+            })
+        );
+
+        assert_eq!(calculator.replay_with(1, 6, 7), Ok(42));
+        assert_eq!(calculator.replay_with(2, 8, 100), Ok(-8));
+        assert_eq!(calculator.history[4].second_num, 0);
+        assert_eq!(
+            calculator.replay_with(1, i64::MAX, 2),
+            Err(CalcError::Overflow {
+                op: OperationType::Multiplication,
+                lhs: i64::MAX,
+                rhs: 2,
+            })
+        );
+        assert_eq!(
+            calculator.replay_with(9, 1, 1),
+            Err(CalcError::InvalidHistoryIndex { index: 9, len: 5 })
+        );
+        assert_eq!(
+            calculator.show_history(),
+            "0: 1 + 2 = 3\n1: 3 * 4 = 12\n2: neg 5 = -5\n3: 6 * 7 = 42\n4: neg 8 = -8\n"
+        );
+    }
+
+    #[test]
+    fn batch_is_atomic() {
+        let mut calculator = Calculator::new();
+        let ops = vec![
+            Operation::new(1, 2, OperationType::Addition),
+            Operation::new(10, 0, OperationType::Division),
+            Operation::new(2, 10, OperationType::Power),
+        ];
+
+        let err = calculator.batch(&ops).unwrap_err();
+        assert_eq!(
+            err,
+            BatchError {
+                position: 1,
+                error: CalcError::DivisionByZero,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "operation 1 of the batch failed: division by zero"
+        );
+        assert!(calculator.history.is_empty());
+
+        assert_eq!(
+            calculator.batch(&[ops[0].clone(), ops[2].clone()]),
+            Ok(vec![3, 1024])
+        );
+        assert_eq!(
+            calculator.show_history(),
+            "0: 1 + 2 = 3\n1: 2 ^ 10 = 1024\n"
+        );
+        assert_eq!(calculator.ans(), Some(&1024));
+        assert!(calculator.undo());
+        assert!(calculator.history.is_empty());
+    }
+
+    #[test]
+    fn history_queries() {
+        let mut calculator = Calculator::new();
+        calculator.evaluate("(2 + 3) * 4 - 2 * 3").unwrap();
+        calculator.negation(2);
+        calculator
+            .history
+            .push(Operation::new(i64::MAX, 1, OperationType::Addition));
+
+        let indexes = |query: HistoryQuery<i64>| query.map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(indexes(calculator.query()), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            indexes(calculator.query().operation(OperationType::Multiplication)),
+            vec![1, 2]
+        );
+        assert_eq!(indexes(calculator.query().result_in(5..=14)), vec![0, 2, 3]);
+        assert_eq!(indexes(calculator.query().result_in(..0)), vec![4]);
+        assert_eq!(indexes(calculator.query().operand(2)), vec![0, 2, 4]);
+        assert_eq!(indexes(calculator.query().operand(0)), Vec::<usize>::new());
+        assert_eq!(
+            indexes(
+                calculator
+                    .query()
+                    .operation(OperationType::Multiplication)
+                    .operand(3)
+            ),
+            vec![2]
+        );
+
+        let stats = calculator.statistics();
+        assert_eq!(stats.count, 6);
+        assert_eq!(stats.count_of(&OperationType::Addition), 2);
+        assert_eq!(stats.count_of(&OperationType::Multiplication), 2);
+        assert_eq!(stats.count_of(&OperationType::Division), 0);
+        assert_eq!(stats.sum, Some(5 + 20 + 6 + 14 - 2));
+        assert_eq!(stats.min, Some(-2));
+        assert_eq!(stats.max, Some(20));
+
+        let stats = calculator.query().result_in(100..).stats();
+        assert_eq!(stats.count, 0);
+        assert_eq!((stats.sum, stats.min, stats.max), (None, None, None));
+    }
+
+    #[test]
+    fn history_formatters() {
+        let mut calculator = Calculator::new();
+        calculator.addition(5, 3);
+        calculator.multiplication(-120, 10);
+        calculator.negation(7);
+        calculator
+            .history
+            .push(Operation::new(i64::MAX, 1, OperationType::Addition));
+
+        assert_eq!(
+            calculator.show_history_with(&PlainFormatter),
+            calculator.show_history()
+        );
+        assert_eq!(
+            calculator.show_history_with(&MarkdownFormatter),
+            "\
+| # | lhs | op | rhs | result |
+|---|---|---|---|---|
+| 0 | 5 | + | 3 | 8 |
+| 1 | -120 | * | 10 | -1200 |
+| 2 | 7 | neg |  | -7 |
+| 3 | 9223372036854775807 | + | 1 | overflow |
+"
+        );
+        assert_eq!(
+            calculator.show_history_with(&JsonLinesFormatter),
+            r#"{"index":0,"id":0,"lhs":5,"op":"+","rhs":3,"result":8}
+{"index":1,"id":1,"lhs":-120,"op":"*","rhs":10,"result":-1200}
+{"index":2,"id":2,"lhs":7,"op":"neg","rhs":null,"result":-7}
+{"index":3,"id":0,"lhs":9223372036854775807,"op":"+","rhs":1,"result":null}
+"#
+        );
+
+        calculator.history.pop();
+        assert_eq!(
+            calculator.show_history_with(&AlignedFormatter),
+            "\
+0:    5 +    3 =     8
+1: -120 *   10 = -1200
+2:      neg  7 =    -7
+"
+        );
+
+        let empty = Calculator::new();
+        assert_eq!(empty.show_history_with(&AlignedFormatter), "");
+        assert_eq!(
+            empty.show_history_with(&MarkdownFormatter),
+            "| # | lhs | op | rhs | result |\n|---|---|---|---|---|\n"
+        );
+    }
+
+    #[test]
+    fn bounded_history_drops_oldest() {
+        let mut calculator = Calculator::with_capacity(3, EvictionPolicy::DropOldest);
+        for i in 1..=5 {
+            calculator.addition(i, i);
+        }
+
+        assert_eq!(calculator.history.len(), 3);
+        assert_eq!(calculator.first_index(), 2);
+        assert_eq!(
+            calculator.show_history(),
+            "2: 3 + 3 = 6\n3: 4 + 4 = 8\n4: 5 + 5 = 10\n"
+        );
+        assert_eq!(calculator.repeat(3), Some(8));
+        assert_eq!(calculator.history[2].repeated_from, Some(3));
+        assert_eq!(
+            calculator.try_repeat(1),
+            Err(CalcError::EvictedHistoryIndex { index: 1, first: 3 })
+        );
+        assert_eq!(
+            calculator.try_repeat(6),
+            Err(CalcError::InvalidHistoryIndex { index: 6, len: 6 })
+        );
+        assert_eq!(
+            calculator.repeat_range(2..4),
+            Err(CalcError::EvictedHistoryIndex { index: 2, first: 3 })
+        );
+        let indexes: Vec<usize> = calculator.query().map(|(i, _)| i).collect();
+        assert_eq!(indexes, vec![3, 4, 5]);
+
+        let mut buffer = Vec::new();
+        calculator
+            .save_history(&mut buffer, HistoryFormat::Csv)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.clone()).unwrap(),
+            "index,lhs,op,rhs,result\n3,4,+,4,8\n4,5,+,5,10\n5,4,+,4,8\n"
+        );
+        let loaded = Calculator::<i64>::load_history(&buffer[..], HistoryFormat::Csv).unwrap();
+        assert_eq!(loaded.first_index(), 3);
+        assert_eq!(loaded.show_history(), calculator.show_history());
+
+        calculator.clear_history();
+        calculator.multiplication(2, 2);
+        assert_eq!(calculator.show_history(), "0: 2 * 2 = 4\n");
+        assert!(calculator.undo());
+        assert!(calculator.undo());
+        assert_eq!(calculator.first_index(), 3);
+        assert_eq!(calculator.repeat(5), Some(8));
+    }
+
+    #[test]
+    fn undo_keeps_indexes_of_bounded_history() {
+        let mut calculator = Calculator::with_capacity(2, EvictionPolicy::DropOldest);
+        calculator.addition(1, 1);
+        calculator.addition(2, 2);
+        calculator.addition(3, 3);
+
+        assert!(calculator.undo());
+        assert!(calculator.undo());
+        // The first addition was evicted, so it can't be taken back.
+        assert!(!calculator.undo());
+        assert_eq!(calculator.show_history(), "");
+        assert!(calculator.redo());
+        assert_eq!(calculator.show_history(), "1: 2 + 2 = 4\n");
+        assert!(calculator.redo());
+        assert_eq!(calculator.show_history(), "1: 2 + 2 = 4\n2: 3 + 3 = 6\n");
+        assert!(!calculator.redo());
+
+        // Half of the evaluation's entries have been evicted; only the
+        // other half comes back, under its old index.
+        calculator.evaluate("2 + 3 + 4").unwrap();
+        calculator.addition(5, 5);
+        assert_eq!(calculator.first_index(), 4);
+        assert!(calculator.undo());
+        assert!(calculator.undo());
+        assert_eq!(calculator.show_history(), "");
+        assert!(calculator.redo());
+        assert_eq!(calculator.show_history(), "4: 5 + 4 = 9\n");
+        assert!(calculator.redo());
+        assert_eq!(calculator.show_history(), "4: 5 + 4 = 9\n5: 5 + 5 = 10\n");
+    }
+
+    #[test]
+    fn save_and_load_bounded_history_json() {
+        let mut calculator = Calculator::with_capacity(2, EvictionPolicy::DropOldest);
+        for i in 1..=3 {
+            calculator.addition(i, i);
+        }
+        calculator.set_capacity(Some(2), EvictionPolicy::RejectNew);
+
+        let mut buffer = Vec::new();
+        calculator
+            .save_history(&mut buffer, HistoryFormat::Json)
+            .unwrap();
+        let mut loaded = Calculator::<i64>::load_history(&buffer[..], HistoryFormat::Json).unwrap();
+        assert_eq!(loaded.show_history(), "1: 2 + 2 = 4\n2: 3 + 3 = 6\n");
+        assert_eq!(loaded.capacity(), Some(2));
+        assert_eq!(
+            loaded.try_repeat(2),
+            Err(CalcError::HistoryFull { capacity: 2 })
+        );
+
+        let too_long = r#"{"capacity":1,"history":[
+            {"first_num":1,"second_num":1,"operation_type":"Addition","result":2,"id":0},
+            {"first_num":2,"second_num":2,"operation_type":"Addition","result":4,"id":1}]}"#;
+        assert!(matches!(
+            Calculator::<i64>::load_history(too_long.as_bytes(), HistoryFormat::Json),
+            Err(PersistError::Malformed { row: 1, .. })
+        ));
+        let too_far = r#"{"first_index":18446744073709551615,"history":[
+            {"first_num":1,"second_num":1,"operation_type":"Addition","result":2,"id":0}]}"#;
+        assert!(matches!(
+            Calculator::<i64>::load_history(too_far.as_bytes(), HistoryFormat::Json),
+            Err(PersistError::Malformed { row: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn bounded_history_rejects_new() {
+        let mut calculator = Calculator::with_capacity(2, EvictionPolicy::RejectNew);
+        calculator.addition(1, 1);
+
+        assert_eq!(
+            calculator.evaluate("1 + 2 + 3"),
+            Err(CalcError::HistoryFull { capacity: 2 })
+        );
+        assert_eq!(
+            calculator.batch(&[
+                Operation::new(1, 2, OperationType::Addition),
+                Operation::new(3, 4, OperationType::Addition),
+            ]),
+            Err(BatchError {
+                position: 1,
+                error: CalcError::HistoryFull { capacity: 2 },
+            })
+        );
+        assert_eq!(calculator.history.len(), 1);
+        assert_eq!(calculator.repeat(0), Some(2));
+        assert_eq!(
+            calculator.try_multiplication(2, 3),
+            Err(CalcError::HistoryFull { capacity: 2 })
+        );
+        assert_eq!(
+            calculator.try_multiplication(2, 3).unwrap_err().to_string(),
+            "history is full (2 entries)"
+        );
+
+        assert!(calculator.undo());
+        calculator
+            .history
+            .push(Operation::new(5, 1, OperationType::Subtraction));
+        assert!(!calculator.redo());
+
+        calculator.set_capacity(Some(1), EvictionPolicy::RejectNew);
+        assert_eq!(calculator.show_history(), "1: 5 - 1 = 4\n");
+        calculator.set_capacity(None, EvictionPolicy::RejectNew);
+        assert_eq!(calculator.evaluate("1 + 2 + 3"), Ok(6));
+    }
+
+    #[test]
+    fn saturating_and_wrapping_modes() {
+        let mut calculator = Calculator::new();
+        calculator.set_mode(ArithmeticMode::Saturating);
+        assert_eq!(calculator.addition(i64::MAX, 1), Some(i64::MAX));
+        assert_eq!(calculator.negation(i64::MIN), Some(i64::MAX));
+        assert_eq!(calculator.addition(1, 1), Some(2));
+        assert_eq!(
+            calculator.try_division(1, 0),
+            Err(CalcError::DivisionByZero)
+        );
+
+        calculator.set_mode(ArithmeticMode::Wrapping);
+        assert_eq!(calculator.evaluate("9223372036854775807 * 2"), Ok(-2));
+        assert_eq!(calculator.power(3, 40), Some(3i64.wrapping_pow(40)));
+
+        calculator.set_mode(ArithmeticMode::Checked);
+        assert_eq!(calculator.repeat(0), Some(i64::MAX));
+        assert_eq!(calculator.replay_with(3, i64::MIN, -1), Ok(i64::MIN));
+        assert_eq!(calculator.addition(i64::MAX, 1), None);
+
+        assert_eq!(calculator.history[2].mode, ArithmeticMode::Saturating);
+        assert!(!calculator.history[2].overflowed);
+        assert_eq!(
+            calculator.show_history(),
+            format!(
+                "0: 9223372036854775807 + 1 = 9223372036854775807 (saturated)\n\
+                 1: neg -9223372036854775808 = 9223372036854775807 (saturated)\n\
+                 2: 1 + 1 = 2\n\
+                 3: 9223372036854775807 * 2 = -2 (wrapped)\n\
+                 4: 3 ^ 40 = {} (wrapped)\n\
+                 5: 9223372036854775807 + 1 = 9223372036854775807 (saturated)\n\
+                 6: -9223372036854775808 * -1 = -9223372036854775808 (wrapped)\n",
+                3i64.wrapping_pow(40)
+            )
+        );
+
+        for format in [HistoryFormat::Json, HistoryFormat::Csv] {
+            let mut buffer = Vec::new();
+            calculator.save_history(&mut buffer, format).unwrap();
+            let loaded = Calculator::<i64>::load_history(&buffer[..], format).unwrap();
+            assert_eq!(loaded.show_history(), calculator.show_history());
+        }
+    }
+
+    #[test]
+    fn modes_without_support_still_overflow() {
+        let mut calculator = Calculator::<Rational>::default();
+        calculator.set_mode(ArithmeticMode::Saturating);
+        let max = Rational::from(i64::MAX);
+        assert_eq!(
+            calculator.try_addition(max, max),
+            Err(CalcError::Overflow {
+                op: OperationType::Addition,
+                lhs: max,
+                rhs: max,
+            })
+        );
+    }
+
+    #[test]
+    fn variables_and_ans_in_expressions() {
+        let mut calculator = Calculator::new();
+
+        assert_eq!(
+            calculator.value_of("ans"),
+            Err(CalcError::UnknownVariable("ans".into()))
+        );
+        assert_eq!(calculator.evaluate("let x = 3 * 4"), Ok(12));
+        assert_eq!(
+            calculator.evaluate("x_2"),
+            Err(CalcError::UnknownVariable("x_2".into()))
+        );
+        calculator.set_variable("x_2", -5).unwrap();
+        assert_eq!(calculator.evaluate("x + x_2 * 2"), Ok(2));
+        assert_eq!(calculator.evaluate("ans ^ 3"), Ok(8));
+        assert_eq!(calculator.ans(), Some(&8));
+
+        let x = calculator.value_of("x").unwrap();
+        assert_eq!(calculator.addition(x, 1), Some(13));
+        assert_eq!(calculator.value_of("ans"), Ok(13));
+
+        assert_eq!(
+            calculator.evaluate("let ans = 1"),
+            Err(CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::ReservedName,
+                span: 4..7,
+            }))
+        );
+        assert_eq!(
+            calculator.evaluate("let y 1"),
+            Err(CalcError::ParseError(ParseError {
+                kind: ParseErrorKind::UnexpectedToken,
+                span: 6..7,
+            }))
+        );
+        assert_eq!(
+            calculator.set_variable("2x", 1),
+            Err(CalcError::InvalidVariableName("2x".into()))
+        );
+        assert_eq!(
+            calculator.set_variable("MR", 1),
+            Err(CalcError::InvalidVariableName("MR".into()))
+        );
+
+        assert_eq!(calculator.remove_variable("x_2"), Some(-5));
+        assert_eq!(calculator.show_variables(), "x = 12\nans = 13\n");
+    }
+
+    #[test]
+    fn memory_register() {
+        let mut calculator = Calculator::new();
+
+        assert_eq!(calculator.memory_recall(), 0);
+        assert_eq!(calculator.memory_add(10), Ok(10));
+        assert_eq!(calculator.memory_subtract(3), Ok(7));
+        assert_eq!(calculator.evaluate("MR * 2"), Ok(14));
+        assert_eq!(
+            calculator.memory_add(i64::MAX),
+            Err(CalcError::Overflow {
+                op: OperationType::Addition,
+                lhs: 7,
+                rhs: i64::MAX,
+            })
+        );
+        assert_eq!(calculator.memory_recall(), 7);
+        assert_eq!(calculator.show_variables(), "ans = 14\nMR = 7\n");
+
+        calculator.memory_clear();
+        assert_eq!(calculator.memory_recall(), 0);
+        assert_eq!(calculator.evaluate("MR + 1"), Ok(1));
+    }
+}
+
+#[cfg(test)]
+mod calculator_properties {
+    use crate::calculator::persist::*;
+    use crate::calculator::*;
+    use proptest::prelude::*;
+
+    const ALL_OPERATIONS: [OperationType; 7] = [
+        OperationType::Addition,
+        OperationType::Subtraction,
+        OperationType::Multiplication,
+        OperationType::Division,
+        OperationType::Remainder,
+        OperationType::Power,
+        OperationType::Negate,
+    ];
+
+    fn operation_type() -> impl Strategy<Value = OperationType> {
+        proptest::sample::select(ALL_OPERATIONS.to_vec())
+    }
+
+    // Mostly small operands, so results are usually defined and the laws
+    // below actually get checked rather than skipped on overflow.
+    fn operand() -> impl Strategy<Value = i64> {
+        prop_oneof![-1000i64..1000, any::<i64>()]
+    }
+
+    /// The reference result for each operation, straight from the
+    /// standard library.
+    fn reference(op: &OperationType, x: i64, y: i64) -> Option<i64> {
+        match op {
+            OperationType::Addition => x.checked_add(y),
+            OperationType::Subtraction => x.checked_sub(y),
+            OperationType::Multiplication => x.checked_mul(y),
+            OperationType::Division => x.checked_div(y),
+            OperationType::Remainder => x.checked_rem(y),
+            OperationType::Power => x.checked_pow(u32::try_from(y).ok()?),
+            OperationType::Negate => x.checked_neg(),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn perform_matches_checked_arithmetic(
+            op in operation_type(),
+            x in operand(),
+            y in operand(),
+        ) {
+            prop_assert_eq!(op.perform(x, y), reference(&op, x, y));
+        }
+
+        #[test]
+        fn sign_round_trips(op in operation_type()) {
+            prop_assert_eq!(OperationType::from_sign(op.get_sign()), Some(op));
+        }
+
+        #[test]
+        fn addition_and_multiplication_commute(x in operand(), y in operand()) {
+            for op in [OperationType::Addition, OperationType::Multiplication] {
+                prop_assert_eq!(op.perform(x, y), op.perform(y, x));
+            }
+        }
+
+        #[test]
+        fn identities(x in operand()) {
+            prop_assert_eq!(OperationType::Addition.perform(x, 0), Some(x));
+            prop_assert_eq!(OperationType::Subtraction.perform(x, 0), Some(x));
+            prop_assert_eq!(OperationType::Multiplication.perform(x, 1), Some(x));
+            prop_assert_eq!(OperationType::Division.perform(x, 1), Some(x));
+            prop_assert_eq!(OperationType::Remainder.perform(x, 1), Some(0));
+            prop_assert_eq!(OperationType::Power.perform(x, 1), Some(x));
+            prop_assert_eq!(OperationType::Power.perform(x, 0), Some(1));
+            if x != i64::MIN {
+                let negated = OperationType::Negate.perform(x, 0).unwrap();
+                prop_assert_eq!(OperationType::Negate.perform(negated, 0), Some(x));
+            }
+        }
+
+        #[test]
+        fn associativity_without_overflow(x in operand(), y in operand(), z in operand()) {
+            for op in [OperationType::Addition, OperationType::Multiplication] {
+                let left = op.perform(x, y).and_then(|xy| op.perform(xy, z));
+                let right = op.perform(y, z).and_then(|yz| op.perform(x, yz));
+                if let (Some(left), Some(right)) = (left, right) {
+                    prop_assert_eq!(left, right);
+                }
+            }
+        }
+
+        #[test]
+        fn subtraction_is_negated_addition(x in operand(), y in operand()) {
+            let difference = OperationType::Subtraction.perform(x, y);
+            let negated = OperationType::Negate
+                .perform(y, 0)
+                .and_then(|minus_y| OperationType::Addition.perform(x, minus_y));
+            if negated.is_some() {
+                prop_assert_eq!(difference, negated);
+            }
+        }
+
+        #[test]
+        fn division_and_remainder_recombine(x in operand(), y in operand()) {
+            let quotient = OperationType::Division.perform(x, y);
+            let remainder = OperationType::Remainder.perform(x, y);
+            if y == 0 {
+                prop_assert_eq!(
+                    OperationType::Division.try_perform(x, y),
+                    Err(CalcError::DivisionByZero)
+                );
+                prop_assert_eq!(remainder, None);
+            } else if let (Some(q), Some(r)) = (quotient, remainder) {
+                prop_assert!(r.unsigned_abs() < y.unsigned_abs());
+                prop_assert_eq!(q.checked_mul(y).and_then(|qy| qy.checked_add(r)), Some(x));
+            }
+        }
+
+        #[test]
+        fn power_adds_exponents(x in -20i64..20, m in 0i64..20, n in 0i64..20) {
+            let whole = OperationType::Power.perform(x, m + n);
+            let split = OperationType::Power
+                .perform(x, m)
+                .zip(OperationType::Power.perform(x, n))
+                .and_then(|(xm, xn)| OperationType::Multiplication.perform(xm, xn));
+            if whole.is_some() {
+                prop_assert_eq!(whole, split);
+            }
+        }
+
+        #[test]
+        fn evaluate_agrees_with_perform(
+            op in operation_type(),
+            x in operand(),
+            y in operand(),
+        ) {
+            let expr = if op.is_unary() {
+                format!("{} ({})", op.get_sign().replace("neg", "-"), x)
+            } else {
+                format!("({}) {} ({})", x, op.get_sign(), y)
+            };
+            let mut calculator = Calculator::new();
+            prop_assert_eq!(calculator.evaluate(&expr).ok(), reference(&op, x, y));
+        }
+
+        #[test]
+        fn history_round_trips(
+            ops in proptest::collection::vec((operation_type(), operand(), operand()), 0..20),
+        ) {
+            let mut calculator = Calculator::new();
+            for (op, x, y) in ops {
+                let _ = calculator.batch(&[Operation::new(x, y, op)]);
+            }
+            for format in [HistoryFormat::Json, HistoryFormat::Csv] {
+                let mut buffer = Vec::new();
+                calculator.save_history(&mut buffer, format).unwrap();
+                let loaded = Calculator::<i64>::load_history(&buffer[..], format).unwrap();
+                prop_assert_eq!(loaded.show_history(), calculator.show_history());
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod shapes_tests {
+    use crate::shapes::serial::*;
+    use crate::shapes::solid::*;
+    use crate::shapes::svg::*;
+    use crate::shapes::transform::*;
+    use crate::shapes::units::*;
     use crate::shapes::*;
     use float_cmp::{assert_approx_eq, F64Margin};
     use rand::Rng;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    use std::time::{Duration, Instant};
 
     // default margin
     const MARGIN: F64Margin = F64Margin {
@@ -351,119 +1727,1297 @@ mod shapes_tests {
     }
 
     #[test]
-    fn circle_area() {
-        let r_in: f64 = 4.0;
-        let circle = Circle::new(r_in).unwrap();
-
-        let computed_area = circle.area(); // an error here will disappear once you implement the area method for Circle
-        let reference_area = area!(r_in);
+    fn circle_area() {
+        let r_in: f64 = 4.0;
+        let circle = Circle::new(r_in).unwrap();
+
+        let computed_area = circle.area(); // an error here will disappear once you implement the area method for Circle
+        let reference_area = area!(r_in);
+
+        assert_approx_eq!(f64, computed_area, reference_area, MARGIN);
+    }
+
+    #[test]
+    fn circle_wrong_input() {
+        let r_in: f64 = 5.0;
+        let mut circle = Circle::new(r_in).unwrap();
+
+        let new_r_in: f64 = -5.0;
+        let res = circle.set_radius(new_r_in);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn circle_area_with_set() {
+        let r_in: f64 = 17.0;
+        let mut circle = Circle::new(r_in).unwrap();
+
+        let computed_area = circle.area(); // an error here will disappear once you implement the Shape trait for Circle
+        let reference_area = area!(r_in);
+
+        assert_approx_eq!(f64, computed_area, reference_area, MARGIN);
+
+        let new_r_in: f64 = 8.0;
+        let res = circle.set_radius(new_r_in); // an error here will disappear once you implement the set_radius method for Circle
+
+        assert!(res.is_ok());
+
+        let computed_area = circle.area(); // an error here will disappear once you implement the Shape trait for Circle
+        let reference_area = area!(new_r_in);
+
+        assert_approx_eq!(f64, computed_area, reference_area, MARGIN);
+    }
+
+    #[test]
+    fn rectangle_circumference() {
+        let width_in: f64 = 15.0;
+        let height_in: f64 = 7.0;
+        let rectangle = Rectangle::new(width_in, height_in).unwrap();
+
+        let computed_circ = rectangle.perimeter();
+        let reference_circ = perimeter!(width_in, height_in);
+
+        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+    }
+
+    #[test]
+    fn rectangle_perimeter_with_set() {
+        let width_in: f64 = 584.0;
+        let height_in: f64 = 1287.0;
+        let mut rectangle = Rectangle::new(width_in, height_in).unwrap();
+
+        let computed_circ = rectangle.perimeter();
+        let reference_circ = perimeter!(width_in, height_in);
+
+        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+
+        let new_width_in: f64 = 8.0;
+        let res = rectangle.set_width(new_width_in);
+        assert!(res.is_ok());
+
+        let computed_circ = rectangle.perimeter();
+        let reference_circ = perimeter!(new_width_in, height_in);
+
+        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+
+        let new_height_in: f64 = 8.0;
+        let res = rectangle.set_height(new_height_in);
+        assert!(res.is_ok());
+
+        let computed_circ = rectangle.perimeter();
+        let reference_circ = perimeter!(new_width_in, new_height_in);
+
+        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+    }
+
+    #[test]
+    fn circle_perimeter() {
+        let r_in: f64 = 7.0;
+        let circle = Circle::new(r_in).unwrap();
+
+        let computed_circ = circle.perimeter(); // an error here will disappear once you implement the Shape trait for Circle
+        let reference_circ = perimeter!(r_in);
+
+        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+    }
+
+    #[test]
+    fn circle_perimeter_with_set() {
+        let r_in: f64 = 3.0;
+        let mut circle = Circle::new(r_in).unwrap();
+
+        let computed_circ = circle.perimeter(); // an error here will disappear once you implement the Shape trait for Circle
+        let reference_circ = perimeter!(r_in);
+
+        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+
+        let new_r_in: f64 = 8.0;
+        let res = circle.set_radius(new_r_in); // an error here will disappear once you implement the set_radius method for Circle
+        assert!(res.is_ok());
+
+        let computed_circ = circle.perimeter(); // an error here will disappear once you implement the Shape trait for Circle
+        let reference_circ = perimeter!(new_r_in);
+
+        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+    }
+
+    #[test]
+    fn square() {
+        let mut square = Square::new(3.0).unwrap();
+        assert_eq!(square.area(), 9.0);
+        assert_eq!(square.perimeter(), 12.0);
+        assert!(square.set_side(5.0).is_ok());
+        assert_eq!(square.get_side(), 5.0);
+        assert_eq!(square.set_side(-1.0), Err(Error::InvalidSide));
+        assert!(Square::new(-2.0).is_err());
+    }
+
+    #[test]
+    fn triangle() {
+        let triangle = Triangle::new(3.0, 4.0, 5.0).unwrap();
+        assert_approx_eq!(f64, triangle.area(), 6.0, MARGIN);
+        assert_eq!(triangle.perimeter(), 12.0);
+
+        let degenerate = Triangle::new(1.0, 2.0, 3.0).unwrap();
+        assert_eq!(degenerate.area(), 0.0);
+
+        // A needle: naive Heron loses all precision here.
+        let needle = Triangle::new(1e8, 1e8, 1.0).unwrap();
+        assert_approx_eq!(f64, needle.area(), 0.5e8, epsilon = 1e-6);
+
+        assert_eq!(
+            Triangle::new(1.0, 2.0, 4.0).err(),
+            Some(Error::InvalidTriangle)
+        );
+        assert_eq!(
+            Triangle::new(-3.0, 4.0, 5.0).err(),
+            Some(Error::InvalidSide)
+        );
+
+        let mut triangle = triangle;
+        assert_eq!(
+            triangle.set_sides(1.0, 1.0, 3.0),
+            Err(Error::InvalidTriangle)
+        );
+        assert_eq!(triangle.get_sides(), (3.0, 4.0, 5.0));
+    }
+
+    #[test]
+    fn ellipse() {
+        let mut ellipse = Ellipse::new(3.0, 2.0).unwrap();
+        assert_approx_eq!(f64, ellipse.area(), 6.0 * std::f64::consts::PI, MARGIN);
+        // Reference value from the complete elliptic integral.
+        assert_approx_eq!(f64, ellipse.perimeter(), 15.865_439_87, epsilon = 1e-6);
+
+        assert!(ellipse.set_radius_y(3.0).is_ok());
+        let r_in: f64 = 3.0;
+        assert_approx_eq!(f64, ellipse.perimeter(), perimeter!(r_in), MARGIN);
+
+        let flat = Ellipse::new(1.0, 0.0).unwrap();
+        assert_approx_eq!(f64, flat.perimeter(), 4.0, epsilon = 2e-3);
+        assert_eq!(Ellipse::new(0.0, 0.0).unwrap().perimeter(), 0.0);
+
+        assert_eq!(ellipse.set_radius_x(-1.0), Err(Error::InvalidRadius));
+        assert_eq!(ellipse.get_radius_x(), 3.0);
+    }
+
+    #[test]
+    fn regular_polygon() {
+        let hexagon = RegularPolygon::new(6, 2.0).unwrap();
+        assert_approx_eq!(f64, hexagon.area(), 6.0 * 3f64.sqrt(), MARGIN);
+        assert_eq!(hexagon.perimeter(), 12.0);
+
+        let square = RegularPolygon::new(4, 3.0).unwrap();
+        assert_approx_eq!(f64, square.area(), 9.0, MARGIN);
+
+        assert_eq!(
+            RegularPolygon::new(2, 1.0).err(),
+            Some(Error::InvalidSideCount)
+        );
+        assert_eq!(RegularPolygon::new(5, -1.0).err(), Some(Error::InvalidSide));
+        assert_eq!(
+            RegularPolygon::new(usize::MAX, 1.0).err(),
+            Some(Error::InvalidSideCount)
+        );
+        let mut many = RegularPolygon::new(RegularPolygon::MAX_SIDES, 1.0).unwrap();
+        assert_eq!(
+            many.set_sides(RegularPolygon::MAX_SIDES + 1),
+            Err(Error::InvalidSideCount)
+        );
+        assert_eq!(many.get_sides(), RegularPolygon::MAX_SIDES);
+    }
+
+    #[test]
+    fn polygon() {
+        let l_shape = Polygon::new(vec![
+            Point::new(0.0, 0.0),
+            Point::new(2.0, 0.0),
+            Point::new(2.0, 1.0),
+            Point::new(1.0, 1.0),
+            Point::new(1.0, 2.0),
+            Point::new(0.0, 2.0),
+        ])
+        .unwrap();
+        assert_eq!(l_shape.area(), 3.0);
+        assert_eq!(l_shape.perimeter(), 8.0);
+        assert_eq!(l_shape.get_vertices().len(), 6);
+
+        let clockwise = Polygon::new(vec![
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 3.0),
+            Point::new(4.0, 0.0),
+        ])
+        .unwrap();
+        assert_eq!(clockwise.area(), 6.0);
+        assert_eq!(clockwise.perimeter(), 12.0);
+
+        assert_eq!(
+            Polygon::new(vec![Point::new(0.0, 0.0), Point::new(1.0, 1.0)]).err(),
+            Some(Error::TooFewVertices)
+        );
+        let bow_tie = vec![
+            Point::new(0.0, 0.0),
+            Point::new(2.0, 2.0),
+            Point::new(2.0, 0.0),
+            Point::new(0.0, 2.0),
+        ];
+        assert_eq!(Polygon::new(bow_tie).err(), Some(Error::SelfIntersecting));
+        let folded = vec![
+            Point::new(0.0, 0.0),
+            Point::new(2.0, 0.0),
+            Point::new(1.0, 0.0),
+            Point::new(1.0, 1.0),
+        ];
+        assert_eq!(Polygon::new(folded).err(), Some(Error::SelfIntersecting));
+        let touching = vec![
+            Point::new(0.0, 0.0),
+            Point::new(4.0, 0.0),
+            Point::new(4.0, 4.0),
+            Point::new(2.0, 0.0),
+            Point::new(0.0, 4.0),
+        ];
+        assert_eq!(Polygon::new(touching).err(), Some(Error::SelfIntersecting));
+    }
+
+    #[test]
+    fn positioned_rectangle() {
+        let rectangle = Rectangle::new(4.0, 2.0)
+            .unwrap()
+            .with_position(Point::new(1.0, 1.0))
+            .unwrap();
+        assert_eq!(rectangle.position(), Point::new(1.0, 1.0));
+        let bounds = rectangle.bounding_box();
+        assert_eq!(bounds.min, Point::new(1.0, 1.0));
+        assert_eq!(bounds.max, Point::new(5.0, 3.0));
+        assert_eq!((bounds.width(), bounds.height()), (4.0, 2.0));
+        assert_eq!(rectangle.centroid(), Point::new(3.0, 2.0));
+        assert!(rectangle.contains(Point::new(2.0, 2.0)));
+        assert!(rectangle.contains(Point::new(5.0, 3.0)));
+        assert!(!rectangle.contains(Point::new(0.0, 2.0)));
+        assert_eq!(rectangle.distance_to(Point::new(3.0, 2.0)), 0.0);
+        assert_eq!(rectangle.distance_to(Point::new(0.0, 2.0)), 1.0);
+        assert_eq!(rectangle.distance_to(Point::new(8.0, 7.0)), 5.0);
+
+        assert_eq!(
+            Rectangle::new(1.0, 1.0).unwrap().position(),
+            Point::new(0.0, 0.0)
+        );
+        let mut rectangle = rectangle;
+        assert_eq!(
+            rectangle.set_position(Point::new(f64::NAN, 0.0)),
+            Err(Error::NonFinite { field: "position" })
+        );
+        assert_eq!(rectangle.position(), Point::new(1.0, 1.0));
+    }
+
+    #[test]
+    fn positioned_circle() {
+        let circle = Circle::new(2.0)
+            .unwrap()
+            .with_position(Point::new(-1.0, 3.0))
+            .unwrap();
+        assert_eq!(circle.centroid(), Point::new(-1.0, 3.0));
+        let bounds = circle.bounding_box();
+        assert_eq!(bounds.min, Point::new(-3.0, 1.0));
+        assert_eq!(bounds.max, Point::new(1.0, 5.0));
+        assert!(circle.contains(Point::new(0.0, 4.0)));
+        assert!(circle.contains(Point::new(1.0, 3.0)));
+        // Inside the bounding box but outside the circle.
+        assert!(!circle.contains(Point::new(0.9, 4.9)));
+        assert_eq!(circle.distance_to(Point::new(-1.0, 3.5)), 0.0);
+        assert_eq!(circle.distance_to(Point::new(2.0, 7.0)), 3.0);
+    }
 
-        assert_approx_eq!(f64, computed_area, reference_area, MARGIN);
+    #[test]
+    fn positioned_shapes() {
+        let square = Square::new(2.0)
+            .unwrap()
+            .with_position(Point::new(1.0, 0.0))
+            .unwrap();
+        assert_eq!(square.centroid(), Point::new(2.0, 1.0));
+        assert_eq!(square.distance_to(Point::new(0.0, 1.0)), 1.0);
+
+        let triangle = Triangle::new(5.0, 4.0, 3.0).unwrap();
+        assert_eq!(
+            triangle.vertices(),
+            [
+                Point::new(0.0, 0.0),
+                Point::new(3.0, 0.0),
+                Point::new(0.0, 4.0)
+            ]
+        );
+        let centroid = triangle.centroid();
+        assert_approx_eq!(f64, centroid.x, 1.0, MARGIN);
+        assert_approx_eq!(f64, centroid.y, 4.0 / 3.0, MARGIN);
+        assert!(triangle.contains(Point::new(1.0, 1.0)));
+        assert!(triangle.contains(Point::new(1.5, 2.0)));
+        assert!(!triangle.contains(Point::new(2.0, 2.0)));
+        assert_approx_eq!(f64, triangle.distance_to(Point::new(3.0, 4.0)), 2.4, MARGIN);
+
+        let ellipse = Ellipse::new(3.0, 1.0)
+            .unwrap()
+            .with_position(Point::new(1.0, 1.0))
+            .unwrap();
+        assert_eq!(ellipse.bounding_box().max, Point::new(4.0, 2.0));
+        assert!(ellipse.contains(Point::new(3.5, 1.0)));
+        assert!(!ellipse.contains(Point::new(3.5, 1.9)));
+        assert_approx_eq!(f64, ellipse.distance_to(Point::new(6.0, 1.0)), 2.0, MARGIN);
+        assert_approx_eq!(f64, ellipse.distance_to(Point::new(1.0, 4.0)), 2.0, MARGIN);
+        // The nearest point of the ellipse to (5, 3) is found numerically;
+        // it must lie on the ellipse and no other point may be closer.
+        let distance = ellipse.distance_to(Point::new(5.0, 3.0));
+        let nearest = (0..10_000)
+            .map(|i| {
+                let t = i as f64 / 10_000.0 * std::f64::consts::TAU;
+                (4.0 - 3.0 * t.cos()).hypot(2.0 - t.sin())
+            })
+            .fold(f64::INFINITY, f64::min);
+        assert!(distance <= nearest && nearest - distance < 1e-6);
+        let flat = Ellipse::new(2.0, 0.0).unwrap();
+        assert!(flat.contains(Point::new(1.0, 0.0)));
+        assert_eq!(flat.distance_to(Point::new(3.0, 0.0)), 1.0);
+
+        let hexagon = RegularPolygon::new(6, 1.0)
+            .unwrap()
+            .with_position(Point::new(2.0, 2.0))
+            .unwrap();
+        assert_eq!(hexagon.centroid(), Point::new(2.0, 2.0));
+        let bounds = hexagon.bounding_box();
+        assert_approx_eq!(f64, bounds.width(), 2.0, MARGIN);
+        assert_approx_eq!(f64, bounds.height(), 3f64.sqrt(), MARGIN);
+        assert!(hexagon.contains(Point::new(2.9, 2.0)));
+        assert!(!hexagon.contains(Point::new(2.9, 2.8)));
+        assert_approx_eq!(f64, hexagon.distance_to(Point::new(4.0, 2.0)), 1.0, MARGIN);
+
+        let l_shape = Polygon::new(vec![
+            Point::new(0.0, 0.0),
+            Point::new(2.0, 0.0),
+            Point::new(2.0, 1.0),
+            Point::new(1.0, 1.0),
+            Point::new(1.0, 2.0),
+            Point::new(0.0, 2.0),
+        ])
+        .unwrap()
+        .with_position(Point::new(1.0, 1.0))
+        .unwrap();
+        assert_eq!(l_shape.get_vertices()[2], Point::new(3.0, 2.0));
+        assert_eq!(l_shape.bounding_box().max, Point::new(3.0, 3.0));
+        let centroid = l_shape.centroid();
+        assert_approx_eq!(f64, centroid.x, 1.0 + 5.0 / 6.0, MARGIN);
+        assert_approx_eq!(f64, centroid.y, 1.0 + 5.0 / 6.0, MARGIN);
+        assert!(l_shape.contains(Point::new(1.5, 2.5)));
+        assert!(l_shape.contains(Point::new(2.0, 2.5)));
+        assert!(!l_shape.contains(Point::new(2.5, 2.5)));
+        assert_eq!(l_shape.distance_to(Point::new(3.0, 3.0)), 1.0);
     }
 
     #[test]
-    fn circle_wrong_input() {
-        let r_in: f64 = 5.0;
-        let mut circle = Circle::new(r_in).unwrap();
+    fn transform_rectangle() {
+        let rectangle = Rectangle::new(4.0, 2.0)
+            .unwrap()
+            .with_position(Point::new(1.0, 1.0))
+            .unwrap();
+        let AnyShape::Rectangle(moved) = rectangle.translate(2.0, 3.0).unwrap() else {
+            panic!("translated rectangle is not a rectangle");
+        };
+        assert_eq!(moved.position(), Point::new(3.0, 4.0));
+        assert_eq!((moved.get_width(), moved.get_height()), (4.0, 2.0));
 
-        let new_r_in: f64 = -5.0;
-        let res = circle.set_radius(new_r_in);
+        let AnyShape::Rectangle(scaled) = rectangle.scale(2.0).unwrap() else {
+            panic!("scaled rectangle is not a rectangle");
+        };
+        assert_eq!(scaled.position(), Point::new(1.0, 1.0));
+        assert_eq!(scaled.area(), 32.0);
 
-        assert!(res.is_err());
+        let AnyShape::Rectangle(turned) = rectangle.rotate(FRAC_PI_2).unwrap() else {
+            panic!("quarter turn of a rectangle is not a rectangle");
+        };
+        assert_eq!(turned.position(), Point::new(-1.0, 1.0));
+        assert_eq!((turned.get_width(), turned.get_height()), (2.0, 4.0));
+        let AnyShape::Rectangle(_) = rectangle.rotate(-3.0 * FRAC_PI_2).unwrap() else {
+            panic!("three quarter turns of a rectangle is not a rectangle");
+        };
+
+        let tilted = rectangle.rotate(FRAC_PI_4).unwrap();
+        let AnyShape::Polygon(polygon) = &tilted else {
+            panic!("tilted rectangle is not a polygon");
+        };
+        assert_eq!(polygon.get_vertices().len(), 4);
+        assert_eq!(tilted.position(), Point::new(1.0, 1.0));
+        assert_approx_eq!(f64, tilted.area(), 8.0, epsilon = 1e-12);
+        assert_approx_eq!(f64, tilted.perimeter(), 12.0, epsilon = 1e-12);
+
+        assert_eq!(rectangle.scale(-1.0).err(), Some(Error::InvalidScale));
+        assert_eq!(
+            rectangle.scale(f64::NAN).err(),
+            Some(Error::NonFinite { field: "factor" })
+        );
+        assert_eq!(
+            rectangle.rotate(f64::INFINITY).err(),
+            Some(Error::NonFinite { field: "angle" })
+        );
+        let strict = Rectangle::new(1.0, 1.0)
+            .unwrap()
+            .with_zero_policy(ZeroPolicy::Reject)
+            .unwrap();
+        assert_eq!(
+            strict.scale(0.0).err(),
+            Some(Error::ZeroSize { field: "height" })
+        );
     }
 
     #[test]
-    fn circle_area_with_set() {
-        let r_in: f64 = 17.0;
-        let mut circle = Circle::new(r_in).unwrap();
+    fn transform_curved_shapes() {
+        let circle = Circle::new(1.0)
+            .unwrap()
+            .with_position(Point::new(2.0, 0.0))
+            .unwrap();
+        let scaled = circle.scale(3.0).unwrap();
+        assert_eq!(
+            scaled,
+            AnyShape::Circle(
+                Circle::new(3.0)
+                    .unwrap()
+                    .with_position(Point::new(2.0, 0.0))
+                    .unwrap()
+            )
+        );
+        let AnyShape::Circle(spun) = circle.rotate(1.0).unwrap() else {
+            panic!("rotated circle is not a circle");
+        };
+        assert_eq!(spun.position(), Point::new(2.0, 0.0));
+        let orbited = circle.transform(&Affine::rotation(FRAC_PI_2)).unwrap();
+        assert_eq!(orbited.position(), Point::new(0.0, 2.0));
+        let stretched = circle.transform(&Affine::scaling(2.0, 1.0)).unwrap();
+        let AnyShape::Ellipse(ellipse) = &stretched else {
+            panic!("stretched circle is not an ellipse");
+        };
+        assert_eq!(ellipse.position(), Point::new(4.0, 0.0));
+        assert_eq!((ellipse.get_radius_x(), ellipse.get_radius_y()), (2.0, 1.0));
 
-        let computed_area = circle.area(); // an error here will disappear once you implement the Shape trait for Circle
-        let reference_area = area!(r_in);
+        let AnyShape::Ellipse(turned) = ellipse.rotate(FRAC_PI_2).unwrap() else {
+            panic!("quarter turn of an ellipse is not an ellipse");
+        };
+        assert_eq!((turned.get_radius_x(), turned.get_radius_y()), (1.0, 2.0));
+        assert_eq!(ellipse.rotate(0.3).err(), Some(Error::UnsupportedTransform));
+        let shear = Affine {
+            c: 1.0,
+            ..Affine::IDENTITY
+        };
+        assert_eq!(
+            circle.transform(&shear).err(),
+            Some(Error::UnsupportedTransform)
+        );
+    }
 
-        assert_approx_eq!(f64, computed_area, reference_area, MARGIN);
+    #[test]
+    fn transform_polygons() {
+        let square = Square::new(2.0).unwrap();
+        let AnyShape::Rectangle(rectangle) = square.transform(&Affine::scaling(2.0, 1.0)).unwrap()
+        else {
+            panic!("stretched square is not a rectangle");
+        };
+        assert_eq!((rectangle.get_width(), rectangle.get_height()), (4.0, 2.0));
+        let AnyShape::Square(_) = square.rotate(PI).unwrap() else {
+            panic!("half turn of a square is not a square");
+        };
 
-        let new_r_in: f64 = 8.0;
-        let res = circle.set_radius(new_r_in); // an error here will disappear once you implement the set_radius method for Circle
+        let triangle = Triangle::new(3.0, 4.0, 5.0).unwrap();
+        let AnyShape::Triangle(doubled) = triangle.scale(2.0).unwrap() else {
+            panic!("scaled triangle is not a triangle");
+        };
+        assert_eq!(doubled.get_sides(), (6.0, 8.0, 10.0));
+        let rotated = triangle.rotate(1.0).unwrap();
+        let AnyShape::Polygon(_) = &rotated else {
+            panic!("rotated triangle is not a polygon");
+        };
+        assert_approx_eq!(f64, rotated.area(), 6.0, epsilon = 1e-12);
+
+        let hexagon = RegularPolygon::new(6, 1.0).unwrap();
+        let flipped = hexagon.transform(&Affine::scaling(1.0, -1.0)).unwrap();
+        assert_approx_eq!(f64, flipped.area(), hexagon.area(), epsilon = 1e-12);
+
+        let polygon = Polygon::new(vec![
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 0.0),
+            Point::new(0.0, 1.0),
+        ])
+        .unwrap();
+        let moved = polygon
+            .transform(&Affine::translation(1.0, 0.0).then(&Affine::rotation(FRAC_PI_2)))
+            .unwrap();
+        let AnyShape::Polygon(moved) = moved else {
+            panic!("transformed polygon is not a polygon");
+        };
+        assert_eq!(
+            moved.get_vertices(),
+            [
+                Point::new(0.0, 1.0),
+                Point::new(0.0, 2.0),
+                Point::new(-1.0, 1.0)
+            ]
+        );
+        let collapse = Affine::scaling(1.0, 0.0);
+        assert_eq!(
+            polygon.transform(&collapse).err(),
+            Some(Error::UnsupportedTransform)
+        );
+        // Shapes the constructors accept but that have no area can't
+        // become a polygon either.
+        let flat = Triangle::new(1.0, 1.0, 2.0).unwrap();
+        assert_eq!(flat.rotate(0.3).err(), Some(Error::UnsupportedTransform));
+        let thin = Rectangle::new(0.0, 2.0).unwrap();
+        assert_eq!(thin.rotate(0.3).err(), Some(Error::UnsupportedTransform));
+        assert!(thin.rotate(FRAC_PI_2).is_ok());
+        let infinite = Affine::translation(f64::INFINITY, 0.0);
+        assert_eq!(
+            polygon.transform(&infinite).err(),
+            Some(Error::NonFinite { field: "transform" })
+        );
+    }
 
-        assert!(res.is_ok());
+    #[test]
+    fn largest_polygons_collide_quickly() {
+        let start = Instant::now();
+        let polygon = RegularPolygon::new(RegularPolygon::MAX_SIDES, 1.0).unwrap();
+        let turned = polygon.rotate(0.3).unwrap();
+        let AnyShape::Polygon(turned) = turned else {
+            panic!("expected a polygon, got {:?}", turned);
+        };
+        assert!(polygon.intersects(&turned));
+        let area = polygon.intersection_area(&turned).unwrap();
+        assert!(area > 0.0 && area <= polygon.area() * (1.0 + 1e-9));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
 
-        let computed_area = circle.area(); // an error here will disappear once you implement the Shape trait for Circle
-        let reference_area = area!(new_r_in);
+    #[test]
+    fn rectangle_and_circle_collisions() {
+        let at = |x, y| Point::new(x, y);
+        let a = Rectangle::new(4.0, 2.0).unwrap();
+        let b = Rectangle::new(2.0, 2.0)
+            .unwrap()
+            .with_position(at(3.0, 1.0))
+            .unwrap();
+        assert!(a.intersects(&b));
+        assert_eq!(a.intersection_area(&b), Ok(1.0));
+        let touching = Rectangle::new(1.0, 1.0)
+            .unwrap()
+            .with_position(at(4.0, 2.0))
+            .unwrap();
+        assert!(a.intersects(&touching));
+        assert_eq!(a.intersection_area(&touching), Ok(0.0));
+        let apart = Square::new(1.0)
+            .unwrap()
+            .with_position(at(5.0, 0.0))
+            .unwrap();
+        assert!(!a.intersects(&apart));
+
+        let unit = Circle::new(1.0).unwrap();
+        let shifted = Circle::new(1.0)
+            .unwrap()
+            .with_position(at(1.0, 0.0))
+            .unwrap();
+        assert!(unit.intersects(&shifted));
+        let lens = 2.0 * PI / 3.0 - 3f64.sqrt() / 2.0;
+        assert_approx_eq!(
+            f64,
+            unit.intersection_area(&shifted).unwrap(),
+            lens,
+            epsilon = 1e-12
+        );
+        let inner = Circle::new(0.5)
+            .unwrap()
+            .with_position(at(0.2, 0.0))
+            .unwrap();
+        assert_approx_eq!(
+            f64,
+            unit.intersection_area(&inner).unwrap(),
+            PI / 4.0,
+            MARGIN
+        );
+        let far = Circle::new(1.0)
+            .unwrap()
+            .with_position(at(2.5, 0.0))
+            .unwrap();
+        assert!(!unit.intersects(&far));
+        assert_eq!(unit.intersection_area(&far), Ok(0.0));
+
+        // A circle centred on a rectangle's corner covers a quarter of it.
+        let corner = Circle::new(1.0)
+            .unwrap()
+            .with_position(at(4.0, 2.0))
+            .unwrap();
+        assert!(a.intersects(&corner));
+        assert_approx_eq!(
+            f64,
+            a.intersection_area(&corner).unwrap(),
+            PI / 4.0,
+            epsilon = 1e-12
+        );
+        assert_approx_eq!(
+            f64,
+            corner.intersection_area(&a).unwrap(),
+            PI / 4.0,
+            epsilon = 1e-12
+        );
+        // Near the corner, but outside the rounded reach of the circle.
+        let diagonal = Circle::new(1.0)
+            .unwrap()
+            .with_position(at(4.8, 2.8))
+            .unwrap();
+        assert!(!a.intersects(&diagonal));
+        // Inscribed: every side of the square is tangent to the circle.
+        let square = Rectangle::new(2.0, 2.0).unwrap();
+        let inscribed = Circle::new(1.0)
+            .unwrap()
+            .with_position(at(1.0, 1.0))
+            .unwrap();
+        assert_approx_eq!(
+            f64,
+            square.intersection_area(&inscribed).unwrap(),
+            PI,
+            epsilon = 1e-12
+        );
+        assert_approx_eq!(
+            f64,
+            inscribed.intersection_area(&square).unwrap(),
+            PI,
+            epsilon = 1e-12
+        );
+        // Tangent to one side from outside.
+        let tangent = Circle::new(1.0)
+            .unwrap()
+            .with_position(at(2.0, 3.0))
+            .unwrap();
+        assert!(a.intersects(&tangent));
+        assert_approx_eq!(
+            f64,
+            a.intersection_area(&tangent).unwrap(),
+            0.0,
+            epsilon = 1e-12
+        );
+        // Tangent to the top side from inside, overlapping the bottom one.
+        let hanging = Circle::new(1.0)
+            .unwrap()
+            .with_position(at(2.0, 1.0))
+            .unwrap();
+        assert_approx_eq!(
+            f64,
+            a.intersection_area(&hanging).unwrap(),
+            PI,
+            epsilon = 1e-12
+        );
+        let covering = Circle::new(10.0).unwrap();
+        assert_approx_eq!(
+            f64,
+            covering.intersection_area(&a).unwrap(),
+            8.0,
+            epsilon = 1e-12
+        );
+    }
 
-        assert_approx_eq!(f64, computed_area, reference_area, MARGIN);
+    #[test]
+    fn polygon_collisions() {
+        let at = |x, y| Point::new(x, y);
+        let triangle = Triangle::new(5.0, 4.0, 3.0).unwrap();
+        let square = Square::new(1.0)
+            .unwrap()
+            .with_position(at(1.0, 1.0))
+            .unwrap();
+        assert!(triangle.intersects(&square));
+        // The hypotenuse x / 3 + y / 4 = 1 cuts the corner off the square.
+        let cut = 1.0 - 0.5 * (2.0 - 1.5) * (2.0 - 4.0 / 3.0);
+        assert_approx_eq!(
+            f64,
+            triangle.intersection_area(&square).unwrap(),
+            cut,
+            epsilon = 1e-12
+        );
+        let beyond = Square::new(1.0)
+            .unwrap()
+            .with_position(at(2.0, 2.0))
+            .unwrap();
+        assert!(!triangle.intersects(&beyond));
+        assert_eq!(triangle.intersection_area(&beyond), Ok(0.0));
+
+        let l_shape = Polygon::new(vec![
+            at(0.0, 0.0),
+            at(2.0, 0.0),
+            at(2.0, 1.0),
+            at(1.0, 1.0),
+            at(1.0, 2.0),
+            at(0.0, 2.0),
+        ])
+        .unwrap();
+        // In the notch of the L, but not touching it.
+        let notch = Square::new(0.5)
+            .unwrap()
+            .with_position(at(1.25, 1.25))
+            .unwrap();
+        assert!(!l_shape.intersects(&notch));
+        let overlapping = Square::new(2.0)
+            .unwrap()
+            .with_position(at(0.5, 0.5))
+            .unwrap();
+        assert!(l_shape.intersects(&overlapping));
+        assert_approx_eq!(
+            f64,
+            l_shape.intersection_area(&overlapping).unwrap(),
+            1.25,
+            epsilon = 1e-12
+        );
+        let reversed: Vec<Point> = l_shape.get_vertices().iter().rev().copied().collect();
+        let reversed = Polygon::new(reversed).unwrap();
+        assert_approx_eq!(
+            f64,
+            reversed.intersection_area(&l_shape).unwrap(),
+            3.0,
+            epsilon = 1e-12
+        );
+        let inside = Square::new(0.5)
+            .unwrap()
+            .with_position(at(0.25, 0.25))
+            .unwrap();
+        assert!(l_shape.intersects(&inside));
+
+        let hexagon = RegularPolygon::new(6, 1.0).unwrap();
+        let circle = Circle::new(1.0).unwrap();
+        assert!(hexagon.intersects(&circle));
+        assert_approx_eq!(
+            f64,
+            hexagon.intersection_area(&circle).unwrap(),
+            hexagon.area(),
+            epsilon = 1e-12
+        );
     }
 
     #[test]
-    fn rectangle_circumference() {
-        let width_in: f64 = 15.0;
-        let height_in: f64 = 7.0;
-        let rectangle = Rectangle::new(width_in, height_in).unwrap();
+    fn ellipse_collisions() {
+        let at = |x, y| Point::new(x, y);
+        let ellipse = Ellipse::new(2.0, 1.0).unwrap();
+        let circle = Circle::new(0.5)
+            .unwrap()
+            .with_position(at(2.4, 0.0))
+            .unwrap();
+        assert!(ellipse.intersects(&circle));
+        let clear = Circle::new(0.5)
+            .unwrap()
+            .with_position(at(2.0, 1.0))
+            .unwrap();
+        assert!(!ellipse.intersects(&clear));
+        assert_eq!(
+            ellipse.intersection_area(&circle),
+            Err(Error::UnsupportedIntersection)
+        );
 
-        let computed_circ = rectangle.perimeter();
-        let reference_circ = perimeter!(width_in, height_in);
+        // The right half of the ellipse.
+        let half = Rectangle::new(3.0, 3.0)
+            .unwrap()
+            .with_position(at(0.0, -1.5))
+            .unwrap();
+        assert!(ellipse.intersects(&half));
+        assert_approx_eq!(
+            f64,
+            ellipse.intersection_area(&half).unwrap(),
+            PI,
+            epsilon = 1e-12
+        );
 
-        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+        // Inscribed in a 4x2 rectangle, touching all four sides.
+        let frame = Rectangle::new(4.0, 2.0)
+            .unwrap()
+            .with_position(at(-2.0, -1.0))
+            .unwrap();
+        assert_approx_eq!(
+            f64,
+            ellipse.intersection_area(&frame).unwrap(),
+            2.0 * PI,
+            epsilon = 1e-12
+        );
+        assert_approx_eq!(
+            f64,
+            frame.intersection_area(&ellipse).unwrap(),
+            2.0 * PI,
+            epsilon = 1e-12
+        );
+        // Tangent to the ellipse's right end from outside.
+        let beside = Square::new(1.0)
+            .unwrap()
+            .with_position(at(2.0, -0.5))
+            .unwrap();
+        assert!(ellipse.intersects(&beside));
+        assert_approx_eq!(
+            f64,
+            ellipse.intersection_area(&beside).unwrap(),
+            0.0,
+            epsilon = 1e-12
+        );
+
+        let similar = Ellipse::new(4.0, 2.0).unwrap();
+        assert_approx_eq!(
+            f64,
+            similar.intersection_area(&ellipse).unwrap(),
+            ellipse.area(),
+            epsilon = 1e-12
+        );
+        let round = Ellipse::new(1.0, 1.0)
+            .unwrap()
+            .with_position(at(5.0, 0.0))
+            .unwrap();
+        assert!(!ellipse.intersects(&round));
+        assert_eq!(ellipse.intersection_area(&round), Ok(0.0));
+        let flat = Ellipse::new(1.0, 0.0)
+            .unwrap()
+            .with_position(at(2.5, 0.0))
+            .unwrap();
+        assert!(ellipse.intersects(&flat));
+        assert_eq!(ellipse.intersection_area(&flat), Ok(0.0));
     }
 
     #[test]
-    fn rectangle_perimeter_with_set() {
-        let width_in: f64 = 584.0;
-        let height_in: f64 = 1287.0;
-        let mut rectangle = Rectangle::new(width_in, height_in).unwrap();
-
-        let computed_circ = rectangle.perimeter();
-        let reference_circ = perimeter!(width_in, height_in);
+    fn solids() {
+        let cuboid = Cuboid::new(2.0, 3.0, 4.0).unwrap();
+        assert_eq!(cuboid.volume(), 24.0);
+        assert_eq!(cuboid.surface_area(), 52.0);
+        assert_eq!(Cuboid::new(1.0, 1.0, -1.0).err(), Some(Error::InvalidDepth));
+
+        let sphere = Sphere::new(3.0).unwrap();
+        assert_approx_eq!(f64, sphere.volume(), 36.0 * PI, MARGIN);
+        assert_approx_eq!(f64, sphere.surface_area(), 36.0 * PI, MARGIN);
+        assert_eq!(Sphere::new(-1.0).err(), Some(Error::InvalidRadius));
+
+        let cylinder = Cylinder::new(2.0, 5.0).unwrap();
+        assert_approx_eq!(f64, cylinder.volume(), 20.0 * PI, MARGIN);
+        assert_approx_eq!(f64, cylinder.surface_area(), 28.0 * PI, MARGIN);
+
+        let cone = Cone::new(3.0, 4.0).unwrap();
+        assert_approx_eq!(f64, cone.volume(), 12.0 * PI, MARGIN);
+        assert_approx_eq!(f64, cone.surface_area(), 24.0 * PI, MARGIN);
+        let mut cone = cone.with_zero_policy(ZeroPolicy::Reject).unwrap();
+        assert_eq!(
+            cone.set_height(0.0),
+            Err(Error::ZeroSize { field: "height" })
+        );
+        assert_eq!(
+            cone.set_radius(f64::NAN),
+            Err(Error::NonFinite { field: "radius" })
+        );
+        assert_eq!(cone.get_height(), 4.0);
+    }
 
-        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+    #[test]
+    fn extrusions() {
+        let rod = Extrusion::new(Circle::new(2.0).unwrap(), 5.0).unwrap();
+        let cylinder = Cylinder::new(2.0, 5.0).unwrap();
+        assert_approx_eq!(f64, rod.volume(), cylinder.volume(), MARGIN);
+        assert_approx_eq!(f64, rod.surface_area(), cylinder.surface_area(), MARGIN);
+        assert_eq!(rod.get_base().get_radius(), 2.0);
+
+        let slab = Extrusion::new(Rectangle::new(2.0, 3.0).unwrap(), 4.0).unwrap();
+        let cuboid = Cuboid::new(2.0, 3.0, 4.0).unwrap();
+        assert_eq!(slab.volume(), cuboid.volume());
+        assert_eq!(slab.surface_area(), cuboid.surface_area());
+
+        let prism = Extrusion::new(Triangle::new(3.0, 4.0, 5.0).unwrap(), 10.0).unwrap();
+        assert_eq!(prism.volume(), 60.0);
+        assert_eq!(prism.surface_area(), 132.0);
+
+        let mut boxed: Extrusion<AnyShape> =
+            Extrusion::new(AnyShape::from(Square::new(1.0).unwrap()), 1.0).unwrap();
+        assert_eq!(boxed.set_height(-1.0), Err(Error::InvalidHeight));
+        assert_eq!(
+            Extrusion::new(Circle::new(1.0).unwrap(), f64::INFINITY).err(),
+            Some(Error::NonFinite { field: "height" })
+        );
+        let solids: Vec<Box<dyn Solid>> =
+            vec![Box::new(boxed), Box::new(Sphere::new(1.0).unwrap())];
+        assert_approx_eq!(
+            f64,
+            solids.iter().map(|solid| solid.volume()).sum::<f64>(),
+            1.0 + 4.0 / 3.0 * PI,
+            MARGIN
+        );
+    }
 
-        let new_width_in: f64 = 8.0;
-        let res = rectangle.set_width(new_width_in);
-        assert!(res.is_ok());
+    #[test]
+    fn units() {
+        let inch = Length::new(1.0, Unit::Inch);
+        assert_eq!(
+            inch.to(Unit::Millimeter),
+            Length::new(25.4, Unit::Millimeter)
+        );
+        assert_approx_eq!(
+            f64,
+            Length::new(3.0, Unit::Foot).to(Unit::Inch).value(),
+            36.0,
+            MARGIN
+        );
+        assert_eq!(Length::new(2.5, Unit::Meter).to(Unit::Meter).value(), 2.5);
+        assert_eq!(Length::new(7.0, Unit::Centimeter).to_string(), "7 cm");
+        assert_eq!(Area::new(21.0, Unit::Millimeter).to_string(), "21 mm²");
+        assert_approx_eq!(
+            f64,
+            Area::new(1.0, Unit::Meter).to(Unit::Centimeter).value(),
+            10_000.0,
+            MARGIN
+        );
+        assert!(Unit::Millimeter.is_metric() && !Unit::Foot.is_metric());
 
-        let computed_circ = rectangle.perimeter();
-        let reference_circ = perimeter!(new_width_in, height_in);
+        let sum = Length::new(1.0, Unit::Centimeter)
+            .plus(Length::new(5.0, Unit::Millimeter), UnitPolicy::Convert)
+            .unwrap();
+        assert_eq!(sum, Length::new(1.5, Unit::Centimeter));
+        assert_eq!(
+            inch.plus(Length::new(1.0, Unit::Meter), UnitPolicy::Reject),
+            Err(Error::UnitMismatch {
+                left: Unit::Inch,
+                right: Unit::Meter
+            })
+        );
+        assert_eq!(
+            inch.plus(inch, UnitPolicy::Reject),
+            Ok(Length::new(2.0, Unit::Inch))
+        );
+    }
 
-        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+    #[test]
+    fn measured_shapes() {
+        let metric = Measured::rectangle(
+            Length::new(70.0, Unit::Millimeter),
+            Length::new(3.0, Unit::Centimeter),
+        )
+        .unwrap();
+        assert_eq!(metric.unit(), Unit::Millimeter);
+        assert_eq!(metric.shape().get_height(), 30.0);
+        assert_eq!(metric.area(), Area::new(2100.0, Unit::Millimeter));
+        assert_eq!(metric.perimeter(), Length::new(200.0, Unit::Millimeter));
+        assert_eq!(
+            Measured::rectangle(Length::new(1.0, Unit::Meter), Length::new(-1.0, Unit::Inch)).err(),
+            Some(Error::InvalidHeight)
+        );
 
-        let new_height_in: f64 = 8.0;
-        let res = rectangle.set_height(new_height_in);
-        assert!(res.is_ok());
+        let imperial = Measured::circle(Length::new(1.0, Unit::Inch)).unwrap();
+        let converted = imperial.to(Unit::Millimeter).unwrap();
+        assert_approx_eq!(f64, converted.area().value(), PI * 25.4 * 25.4, MARGIN);
+        assert_approx_eq!(
+            f64,
+            imperial.area().to(Unit::Millimeter).value(),
+            converted.area().value(),
+            MARGIN
+        );
+        let total = metric
+            .area()
+            .plus(imperial.area(), UnitPolicy::Convert)
+            .unwrap();
+        assert_approx_eq!(f64, total.value(), 2100.0 + PI * 25.4 * 25.4, MARGIN);
+        assert_eq!(
+            metric.area().plus(imperial.area(), UnitPolicy::Reject),
+            Err(Error::UnitMismatch {
+                left: Unit::Millimeter,
+                right: Unit::Inch
+            })
+        );
 
-        let computed_circ = rectangle.perimeter();
-        let reference_circ = perimeter!(new_width_in, new_height_in);
+        // A 1 in circle centred on the rectangle's lower-left corner.
+        let overlap = metric
+            .intersection_area(&imperial, UnitPolicy::Convert)
+            .unwrap();
+        assert_eq!(overlap.unit(), Unit::Millimeter);
+        assert_approx_eq!(f64, overlap.value(), PI * 25.4 * 25.4 / 4.0, epsilon = 1e-9);
+        assert_eq!(
+            metric
+                .intersection_area(&imperial, UnitPolicy::Reject)
+                .err(),
+            Some(Error::UnitMismatch {
+                left: Unit::Millimeter,
+                right: Unit::Inch
+            })
+        );
+    }
 
-        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+    #[test]
+    fn shapes_to_json() {
+        let circle = Circle::new(3.0).unwrap();
+        assert_eq!(
+            serde_json::to_string(&circle).unwrap(),
+            r#"{"type":"circle","radius":3.0}"#
+        );
+        let circle: Circle = serde_json::from_str(r#"{"type":"circle","radius":3}"#).unwrap();
+        assert_eq!(circle.get_radius(), 3.0);
+
+        let shapes = vec![
+            AnyShape::from(
+                Rectangle::new(7.0, 3.0)
+                    .unwrap()
+                    .with_position(Point::new(1.0, -2.0))
+                    .unwrap(),
+            ),
+            Square::new(2.0)
+                .unwrap()
+                .with_zero_policy(ZeroPolicy::Reject)
+                .unwrap()
+                .into(),
+            Triangle::new(3.0, 4.0, 5.0).unwrap().into(),
+            Ellipse::new(2.0, 1.0).unwrap().into(),
+            RegularPolygon::new(6, 1.5).unwrap().into(),
+            Polygon::new(vec![
+                Point::new(0.0, 0.0),
+                Point::new(4.0, 0.0),
+                Point::new(0.0, 3.0),
+            ])
+            .unwrap()
+            .into(),
+        ];
+        let json = serde_json::to_string(&shapes).unwrap();
+        assert!(json.starts_with(
+            r#"[{"type":"rectangle","width":7.0,"height":3.0,"position":{"x":1.0,"y":-2.0}},"#
+        ));
+        assert!(json.contains(r#"{"type":"square","side":2.0,"zero_policy":"reject"}"#));
+        let loaded: Vec<AnyShape> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, shapes);
     }
 
     #[test]
-    fn circle_perimeter() {
-        let r_in: f64 = 7.0;
-        let circle = Circle::new(r_in).unwrap();
+    fn invalid_json_shapes() {
+        let err = serde_json::from_str::<AnyShape>(r#"{"type":"circle","radius":-3}"#)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            ParseShapeError::Invalid(Error::InvalidRadius).to_string()
+        );
+        let err = serde_json::from_str::<AnyShape>(
+            r#"{"type":"rectangle","width":0,"height":1,"zero_policy":"reject"}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "width must not be zero");
+        let err = serde_json::from_str::<Rectangle>(r#"{"type":"circle","radius":1}"#).unwrap_err();
+        assert_eq!(err.to_string(), "expected a rectangle, found a circle");
+        let err = serde_json::from_str::<AnyShape>(
+            r#"{"type":"regular_polygon","sides":18446744073709551615,"side_length":1}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), Error::InvalidSideCount.to_string());
+        assert!(serde_json::from_str::<AnyShape>(r#"{"type":"blob"}"#).is_err());
+        assert!(serde_json::from_str::<AnyShape>(r#"{"type":"circle"}"#).is_err());
+    }
 
-        let computed_circ = circle.perimeter(); // an error here will disappear once you implement the Shape trait for Circle
-        let reference_circ = perimeter!(r_in);
+    #[test]
+    fn shape_text_syntax() {
+        let rectangle: AnyShape = "rect 7x3".parse().unwrap();
+        assert_eq!(rectangle, Rectangle::new(7.0, 3.0).unwrap().into());
+        let circle: AnyShape = "circle r=17".parse().unwrap();
+        assert_eq!(circle, Circle::new(17.0).unwrap().into());
+        let placed: AnyShape = "  square 2.5   at -1,4 ".parse().unwrap();
+        assert_eq!(placed.position(), Point::new(-1.0, 4.0));
+        assert_eq!(placed.area(), 6.25);
+
+        for text in [
+            "rect 7x3",
+            "rect 0.5x0.001 at 1,2",
+            "square 4",
+            "circle r=17 at 0.5,-0.5",
+            "ellipse 3x2",
+            "triangle 3,4,5",
+            "regular n=6 s=2",
+            "polygon 0,0 4,0 0,3",
+        ] {
+            let shape: AnyShape = text.parse().unwrap();
+            assert_eq!(shape.to_string(), text);
+        }
 
-        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+        let parse = |text: &str| text.parse::<AnyShape>().unwrap_err();
+        assert_eq!(
+            parse("rect -7x3"),
+            ParseShapeError::Invalid(Error::InvalidWidth)
+        );
+        assert_eq!(
+            parse("circle r=-1"),
+            ParseShapeError::Invalid(Error::InvalidRadius)
+        );
+        assert_eq!(
+            parse("circle r=NaN"),
+            ParseShapeError::Invalid(Error::NonFinite { field: "radius" })
+        );
+        assert_eq!(
+            parse("triangle 1,1,3"),
+            ParseShapeError::Invalid(Error::InvalidTriangle)
+        );
+        assert_eq!(
+            parse("regular n=2 s=1"),
+            ParseShapeError::Invalid(Error::InvalidSideCount)
+        );
+        assert_eq!(
+            parse("regular n=18446744073709551615 s=1"),
+            ParseShapeError::Invalid(Error::InvalidSideCount)
+        );
+        assert_eq!(
+            parse("polygon 0,0 1,1"),
+            ParseShapeError::Invalid(Error::TooFewVertices)
+        );
+        assert_eq!(
+            parse("square 1 at inf,0"),
+            ParseShapeError::Invalid(Error::NonFinite { field: "position" })
+        );
+        assert_eq!(
+            parse(""),
+            ParseShapeError::Syntax("expected a shape".to_string())
+        );
+        assert_eq!(
+            parse("hexagon 3"),
+            ParseShapeError::Syntax("unknown shape \"hexagon\"".to_string())
+        );
+        assert_eq!(
+            parse("circle 17"),
+            ParseShapeError::Syntax("expected r=..., found \"17\"".to_string())
+        );
+        assert_eq!(
+            parse("rect 7"),
+            ParseShapeError::Syntax(
+                "expected two numbers separated by 'x', found \"7\"".to_string()
+            )
+        );
+        assert_eq!(
+            parse("square 1 2"),
+            ParseShapeError::Syntax("wrong number of values for square".to_string())
+        );
     }
 
     #[test]
-    fn circle_perimeter_with_set() {
-        let r_in: f64 = 3.0;
-        let mut circle = Circle::new(r_in).unwrap();
+    fn svg_rendering() {
+        let rectangle = Rectangle::new(7.0, 3.0).unwrap();
+        let circle = Circle::new(1.5)
+            .unwrap()
+            .with_position(Point::new(9.0, 1.0))
+            .unwrap();
+        let triangle = Triangle::new(5.0, 4.0, 3.0)
+            .unwrap()
+            .with_position(Point::new(0.0, -5.0))
+            .unwrap();
+        let ellipse = Ellipse::new(2.0, 0.5).unwrap();
+        let expected = "\
+<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-3 -4 14.5 10\" width=\"14.5\" height=\"10\">
+  <rect x=\"0\" y=\"-3\" width=\"7\" height=\"3\" fill=\"none\" stroke=\"black\" stroke-width=\"1\"/>
+  <circle cx=\"9\" cy=\"-1\" r=\"1.5\" fill=\"none\" stroke=\"black\" stroke-width=\"1\"/>
+  <polygon points=\"0,5 3,5 0,1\" fill=\"none\" stroke=\"black\" stroke-width=\"1\"/>
+  <ellipse cx=\"0\" cy=\"0\" rx=\"2\" ry=\"0.5\" fill=\"none\" stroke=\"black\" stroke-width=\"1\"/>
+</svg>
+";
+        assert_eq!(
+            render(&[&rectangle, &circle, &triangle, &ellipse]),
+            expected
+        );
 
-        let computed_circ = circle.perimeter(); // an error here will disappear once you implement the Shape trait for Circle
-        let reference_circ = perimeter!(r_in);
+        let highlight = Style {
+            fill: "#ffcc00".to_string(),
+            stroke: "a\"b<c>&".to_string(),
+            stroke_width: 0.25,
+        };
+        let third = Square::new(1.0 / 3.0).unwrap();
+        let mut svg = Svg::new().with_margin(0.0).with_style(Style {
+            stroke_width: 0.1,
+            ..Style::default()
+        });
+        svg.add(&third).add_styled(&rectangle, highlight);
+        let expected = "\
+<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 -3 7 3\" width=\"7\" height=\"3\">
+  <rect x=\"0\" y=\"-0.333333\" width=\"0.333333\" height=\"0.333333\" fill=\"none\" stroke=\"black\" stroke-width=\"0.1\"/>
+  <rect x=\"0\" y=\"-3\" width=\"7\" height=\"3\" fill=\"#ffcc00\" stroke=\"a&quot;b&lt;c&gt;&amp;\" stroke-width=\"0.25\"/>
+</svg>
+";
+        assert_eq!(svg.render(), expected);
+        assert_eq!(svg.render(), expected);
+
+        let rotated = rectangle.rotate(FRAC_PI_4).unwrap();
+        assert!(render(&[&rotated]).contains("<polygon points=\"0,0 4.949747,-4.949747 "));
+        assert_eq!(
+            render(&[]),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-1 -1 2 2\" width=\"2\" height=\"2\">\n</svg>\n"
+        );
+    }
 
-        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+    #[test]
+    fn non_finite_dimensions() {
+        let non_finite = |field| Some(Error::NonFinite { field });
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(Rectangle::new(value, 1.0).err(), non_finite("width"));
+            assert_eq!(Rectangle::new(1.0, value).err(), non_finite("height"));
+            assert_eq!(Circle::new(value).err(), non_finite("radius"));
+            assert_eq!(Square::new(value).err(), non_finite("side"));
+            assert_eq!(Triangle::new(3.0, value, 5.0).err(), non_finite("side"));
+            assert_eq!(Ellipse::new(1.0, value).err(), non_finite("radius_y"));
+            assert_eq!(
+                RegularPolygon::new(5, value).err(),
+                non_finite("side_length")
+            );
+            let vertices = vec![
+                Point::new(0.0, 0.0),
+                Point::new(1.0, value),
+                Point::new(1.0, 0.0),
+            ];
+            assert_eq!(Polygon::new(vertices).err(), non_finite("vertex"));
+        }
 
-        let new_r_in: f64 = 8.0;
-        let res = circle.set_radius(new_r_in); // an error here will disappear once you implement the set_radius method for Circle
-        assert!(res.is_ok());
+        let mut rectangle = Rectangle::new(2.0, 3.0).unwrap();
+        assert_eq!(
+            rectangle.set_width(f64::NAN),
+            Err(non_finite("width").unwrap())
+        );
+        assert_eq!(
+            rectangle.set_height(f64::INFINITY),
+            Err(non_finite("height").unwrap())
+        );
+        assert_eq!(rectangle.area(), 6.0);
+        let mut circle = Circle::new(1.0).unwrap();
+        assert_eq!(
+            circle.set_radius(f64::NAN),
+            Err(non_finite("radius").unwrap())
+        );
+        assert_eq!(circle.get_radius(), 1.0);
+    }
 
-        let computed_circ = circle.perimeter(); // an error here will disappear once you implement the Shape trait for Circle
-        let reference_circ = perimeter!(new_r_in);
+    #[test]
+    fn zero_policy() {
+        let zero = |field| Some(Error::ZeroSize { field });
+        assert_eq!(Rectangle::new(0.0, 0.0).unwrap().area(), 0.0);
+        assert_eq!(
+            Rectangle::new(0.0, 1.0)
+                .unwrap()
+                .with_zero_policy(ZeroPolicy::Reject)
+                .err(),
+            zero("width")
+        );
+        assert_eq!(
+            Circle::new(0.0)
+                .unwrap()
+                .with_zero_policy(ZeroPolicy::Reject)
+                .err(),
+            zero("radius")
+        );
+        assert_eq!(
+            Triangle::new(1.0, 2.0, 3.0)
+                .unwrap()
+                .with_zero_policy(ZeroPolicy::Reject)
+                .err(),
+            zero("area")
+        );
 
-        assert_approx_eq!(f64, computed_circ, reference_circ, MARGIN);
+        let mut rectangle = Rectangle::new(2.0, 3.0)
+            .unwrap()
+            .with_zero_policy(ZeroPolicy::Reject)
+            .unwrap();
+        assert_eq!(rectangle.set_height(0.0), Err(zero("height").unwrap()));
+        assert_eq!(rectangle.set_width(-1.0), Err(Error::InvalidWidth));
+        assert_eq!(rectangle.area(), 6.0);
+        let mut ellipse = Ellipse::new(1.0, 2.0)
+            .unwrap()
+            .with_zero_policy(ZeroPolicy::Reject)
+            .unwrap();
+        assert_eq!(ellipse.set_radius_x(0.0), Err(zero("radius_x").unwrap()));
+        let mut ellipse = ellipse.with_zero_policy(ZeroPolicy::Allow).unwrap();
+        assert_eq!(ellipse.set_radius_x(0.0), Ok(()));
+        assert_eq!(ellipse.area(), 0.0);
     }
 
     #[test]
@@ -516,3 +3070,128 @@ mod shapes_tests {
         }
     }
 }
+
+#[cfg(test)]
+mod repl_tests {
+    use crate::repl::Repl;
+
+    fn run_script(script: &str) -> String {
+        let mut output = Vec::new();
+        Repl::new()
+            .run(script.as_bytes(), &mut output, false)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn evaluates_lines_and_commands() {
+        let script = "\
+# comments and blank lines are skipped
+
+(3 + 4) * -2 - 7
+:history
+:repeat 0
+:undo
+:clear
+:history
+";
+        let expected = "\
+-21
+0: 3 + 4 = 7
+1: 7 * -2 = -14
+2: -14 - 7 = -21
+7
+undone
+history cleared
+history is empty
+";
+        assert_eq!(run_script(script), expected);
+    }
+
+    #[test]
+    fn reports_errors_and_continues() {
+        let script = "1 / 0\n:repeat 3\n:repeat x\n:save\n:frobnicate\n2 +\n6 * 7\n";
+        let expected = "\
+error: division by zero
+error: history index 3 is out of range for 0 entries
+error: invalid history index \"x\"
+error: :save needs an argument
+error: unknown command :frobnicate (try :help)
+error: unexpected end of input at 3..3
+42
+";
+        assert_eq!(run_script(script), expected);
+    }
+
+    #[test]
+    fn variables_and_memory() {
+        let script = "let rate = 3\n:vars\nrate * 4\n:m+\n:m- rate\nMR\n:mc\n:vars\n";
+        let expected = "\
+3
+rate = 3
+ans = 3
+12
+MR = 12
+MR = 9
+9
+memory cleared
+rate = 3
+ans = 9
+";
+        assert_eq!(run_script(script), expected);
+    }
+
+    #[test]
+    fn quit_stops_reading() {
+        assert_eq!(run_script("1 + 1\n:quit\n2 + 2\n"), "2\n");
+    }
+
+    #[test]
+    fn save_and_load_files() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        for name in [
+            format!("task2-repl-{}.json", id),
+            format!("task2-repl-{}.csv", id),
+        ] {
+            let path = dir.join(name);
+            let path = path.to_str().unwrap();
+
+            let mut repl = Repl::new();
+            repl.handle_line("2 ^ 5");
+            assert_eq!(
+                repl.handle_line(&format!(":save {}", path)),
+                Some(format!("saved 1 entries to {}", path))
+            );
+
+            let mut repl = Repl::new();
+            repl.handle_line("let x = 7");
+            repl.handle_line(":m+");
+            assert_eq!(
+                repl.handle_line(&format!(":load {}", path)),
+                Some(format!("loaded 1 entries from {}", path))
+            );
+            assert_eq!(
+                repl.handle_line(":history"),
+                Some("0: 2 ^ 5 = 32".to_string())
+            );
+            // The rest of the session is kept, but the load can't be undone.
+            assert_eq!(repl.handle_line("x + MR + ans"), Some("21".to_string()));
+            repl.handle_line(":undo");
+            assert_eq!(
+                repl.handle_line(":undo"),
+                Some("nothing to undo".to_string())
+            );
+            assert_eq!(
+                repl.handle_line(":history"),
+                Some("0: 2 ^ 5 = 32".to_string())
+            );
+            assert!(repl
+                .handle_line(":load /nonexistent/history.json")
+                .unwrap()
+                .starts_with("error: "));
+            assert_eq!(repl.handle_line("x"), Some("7".to_string()));
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}